@@ -0,0 +1,79 @@
+// Keep in sync with `bevy_winit_gtk/src/clean_windows.rs`: both backends render the same way.
+
+//! Skip rendering the windows that are not in [`WindowsToRedraw`].
+//!
+//! The renderer draws and presents every extracted window. Clean windows are taken out
+//! of [`ExtractedWindows`] before their surface texture is acquired, so that neither
+//! their cameras nor the clear pass draw to them, and they keep showing their last
+//! frame. They are put back once the frame is rendered, so that the next extraction
+//! still sees their previous size and present mode.
+use bevy::app::{App, Plugin};
+use bevy::ecs::prelude::*;
+use bevy::render::view::{ExtractedWindow, ExtractedWindows, WindowSystem};
+use bevy::render::{Extract, ExtractSchedule, Render, RenderApp, RenderSet};
+use bevy::utils::{HashMap, HashSet};
+use bevy::window::Window;
+
+use crate::WindowsToRedraw;
+
+/// The windows skipped by the renderer this frame.
+#[derive(Resource, Default)]
+struct CleanWindows {
+    entities: HashSet<Entity>,
+    skipped: HashMap<Entity, ExtractedWindow>,
+}
+
+pub(crate) struct CleanWindowsPlugin;
+impl Plugin for CleanWindowsPlugin {
+    fn build(&self, _app: &mut App) {}
+
+    fn finish(&self, app: &mut App) {
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<CleanWindows>()
+            .add_systems(ExtractSchedule, extract_clean_windows)
+            .add_systems(
+                Render,
+                (
+                    skip_clean_windows
+                        .in_set(RenderSet::ManageViews)
+                        .before(WindowSystem::Prepare),
+                    restore_clean_windows.in_set(RenderSet::Cleanup),
+                ),
+            );
+    }
+}
+
+/// Find the windows that don't need a redraw: not in [`WindowsToRedraw`], and unchanged.
+///
+/// Without [`WindowsToRedraw`], when the app isn't run by our runner, all windows are drawn.
+fn extract_clean_windows(
+    mut clean: ResMut<CleanWindows>,
+    to_redraw: Extract<Option<Res<WindowsToRedraw>>>,
+    windows: Extract<Query<(Entity, Ref<Window>)>>,
+) {
+    clean.entities.clear();
+    let Some(to_redraw) = to_redraw.as_ref() else {
+        return;
+    };
+    let unchanged = windows.iter().filter(|(_, window)| !window.is_changed());
+    let entities = unchanged
+        .map(|(entity, _)| entity)
+        .filter(|entity| !to_redraw.contains(*entity));
+    clean.entities.extend(entities);
+}
+
+fn skip_clean_windows(mut clean: ResMut<CleanWindows>, mut windows: ResMut<ExtractedWindows>) {
+    let clean = &mut *clean;
+    for entity in &clean.entities {
+        if let Some(window) = windows.windows.remove(entity) {
+            clean.skipped.insert(*entity, window);
+        }
+    }
+}
+
+fn restore_clean_windows(mut clean: ResMut<CleanWindows>, mut windows: ResMut<ExtractedWindows>) {
+    windows.windows.extend(clean.skipped.drain());
+}
@@ -11,6 +11,10 @@ use bevy::window::{CursorIcon, WindowLevel, WindowTheme};
 use tao::window::Window;
 use tao::{event::KeyEvent, keyboard::Key};
 
+#[cfg(target_os = "linux")]
+use crate::ResizeEdge;
+use crate::UserAttention;
+
 pub fn convert_keyboard_input(keyboard_input: &KeyEvent, window: Entity) -> KeyboardInput {
     KeyboardInput {
         scan_code: keyboard_input.physical_key.to_scancode().unwrap(),
@@ -260,3 +264,35 @@ pub fn convert_window_theme(theme: WindowTheme) -> tao::window::Theme {
         WindowTheme::Dark => tao::window::Theme::Dark,
     }
 }
+
+#[cfg(target_os = "linux")]
+pub fn convert_resize_edge(edge: ResizeEdge) -> gtk::gdk::WindowEdge {
+    match edge {
+        ResizeEdge::North => gtk::gdk::WindowEdge::North,
+        ResizeEdge::South => gtk::gdk::WindowEdge::South,
+        ResizeEdge::East => gtk::gdk::WindowEdge::East,
+        ResizeEdge::West => gtk::gdk::WindowEdge::West,
+        ResizeEdge::NorthEast => gtk::gdk::WindowEdge::NorthEast,
+        ResizeEdge::NorthWest => gtk::gdk::WindowEdge::NorthWest,
+        ResizeEdge::SouthEast => gtk::gdk::WindowEdge::SouthEast,
+        ResizeEdge::SouthWest => gtk::gdk::WindowEdge::SouthWest,
+    }
+}
+
+/// The X11/GDK button number for a bevy mouse button.
+#[cfg(target_os = "linux")]
+pub fn convert_gtk_button(button: MouseButton) -> i32 {
+    match button {
+        MouseButton::Left => 1,
+        MouseButton::Middle => 2,
+        MouseButton::Right => 3,
+        MouseButton::Other(button) => i32::from(button),
+    }
+}
+
+pub fn convert_user_attention(attention: UserAttention) -> tao::window::UserAttentionType {
+    match attention {
+        UserAttention::Critical => tao::window::UserAttentionType::Critical,
+        UserAttention::Informational => tao::window::UserAttentionType::Informational,
+    }
+}
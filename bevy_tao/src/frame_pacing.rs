@@ -0,0 +1,50 @@
+// Keep in sync with `bevy_winit_gtk/src/frame_pacing.rs`: both backends pace frames the same way,
+// and report it under the same diagnostic.
+
+//! Frame rate limits, and how precisely the runner applies them.
+use bevy::diagnostic::{DiagnosticId, DiagnosticMeasurement, DiagnosticsStore};
+use bevy::ecs::world::World;
+use bevy::utils::{Duration, Instant};
+
+/// How late app updates start compared to when they were due, in milliseconds.
+///
+/// Only measured when the frame rate is limited, see `frame_rate_limit` and
+/// `background_frame_rate_limit` in the settings of the backend.
+pub const FRAME_PACING_JITTER: DiagnosticId =
+    DiagnosticId::from_u128(0x6a4d_43b1_9e0f_4c8e_a1c2_58f3_7d1e_20b9);
+
+/// The minimum time between two frames for a frame rate `limit`, in frames per second.
+///
+/// Limits that are not strictly positive, or too low for a [`Duration`], don't limit anything.
+pub(crate) fn frame_duration(limit: Option<f64>) -> Option<Duration> {
+    let limit = limit.filter(|limit| *limit > 0.0)?;
+    Duration::try_from_secs_f64(limit.recip()).ok()
+}
+
+/// Add the time elapsed between when a frame was due and when it started to [`FRAME_PACING_JITTER`].
+pub(crate) fn record_frame_pacing_jitter(world: &mut World, now: Instant, jitter: Duration) {
+    let mut diagnostics = world.resource_mut::<DiagnosticsStore>();
+    let diagnostic = diagnostics.get_mut(FRAME_PACING_JITTER);
+    if let Some(diagnostic) = diagnostic.filter(|diagnostic| diagnostic.is_enabled) {
+        diagnostic.add_measurement(DiagnosticMeasurement {
+            time: now,
+            value: jitter.as_secs_f64() * 1000.0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_duration_of_limits() {
+        assert_eq!(frame_duration(None), None);
+        assert_eq!(frame_duration(Some(4.0)), Some(Duration::from_millis(250)));
+        assert_eq!(frame_duration(Some(0.0)), None);
+        assert_eq!(frame_duration(Some(-60.0)), None);
+        assert_eq!(frame_duration(Some(f64::NAN)), None);
+        assert_eq!(frame_duration(Some(1e-300)), None);
+        assert_eq!(frame_duration(Some(f64::INFINITY)), Some(Duration::ZERO));
+    }
+}
@@ -7,22 +7,33 @@
 //! See `tao_runner` for details.
 
 // pub mod accessibility;
+#[cfg(feature = "render")]
+mod clean_windows;
 mod converters;
+mod frame_pacing;
 mod system;
 mod tao_config;
 mod tao_windows;
+mod window_requests;
 
 use std::marker::PhantomData;
 
 use bevy::ecs::system::{SystemParam, SystemState};
 use bevy::tasks::tick_global_task_pools_on_main_thread;
-use system::{changed_window, create_window, despawn_window, CachedWindow};
+use frame_pacing::record_frame_pacing_jitter;
+use system::{
+    changed_window, clear_attention_on_focus, create_window, despawn_window,
+    pending_title_bar_moves, CachedWindow,
+};
 
+pub use frame_pacing::FRAME_PACING_JITTER;
 pub use tao::window::Window as TaoWindow;
 pub use tao_config::*;
 pub use tao_windows::*;
+pub use window_requests::*;
 
 use bevy::app::{App, AppExit, Last, Plugin};
+use bevy::diagnostic::{Diagnostic, RegisterDiagnostic};
 use bevy::ecs::event::{Events, ManualEventReader};
 use bevy::ecs::prelude::*;
 use bevy::input::{
@@ -32,7 +43,7 @@ use bevy::input::{
 };
 use bevy::log::{error, info, trace};
 use bevy::math::{ivec2, DVec2, Vec2};
-use bevy::utils::Instant;
+use bevy::utils::{HashMap, HashSet, Instant};
 use bevy::window::{
     exit_on_all_closed, CursorEntered, CursorLeft, CursorMoved, FileDragAndDrop, ReceivedCharacter,
     RequestRedraw, Window, WindowBackendScaleFactorChanged, WindowCloseRequested, WindowCreated,
@@ -79,18 +90,28 @@ impl<W: GetWindow + 'static> Plugin for TaoPlugin<W> {
 
         app.init_non_send_resource::<TaoWindows<W>>()
             .init_resource::<TaoSettings>()
+            .init_resource::<WindowsToRedraw>()
+            .add_event::<RequestWindowRedraw>()
+            .register_diagnostic(
+                Diagnostic::new(FRAME_PACING_JITTER, "frame_pacing_jitter", 20).with_suffix("ms"),
+            )
             .set_runner(tao_runner::<W>)
             // exit_on_all_closed only uses the query to determine if the query is empty,
             // and so doesn't care about ordering relative to changed_window
             .add_systems(
                 Last,
                 (
+                    clear_attention_on_focus.before(changed_window::<W>),
+                    pending_title_bar_moves.before(changed_window::<W>),
                     changed_window::<W>.ambiguous_with(exit_on_all_closed),
                     // Update the state of the window before attempting to despawn to ensure consistent event ordering
                     despawn_window::<W>.after(changed_window::<W>),
                 ),
             );
 
+        #[cfg(feature = "render")]
+        app.add_plugins(clean_windows::CleanWindowsPlugin);
+
         let mut create_window_system_state: SystemState<(
             Commands,
             NonSendMut<EventLoop<()>>,
@@ -217,6 +238,13 @@ struct TaoPersistentState {
     /// timeout.
     timeout_reached: bool,
     last_update: Instant,
+    /// Per-window state, used to only update the app when a window needs it.
+    windows: HashMap<Entity, WindowWakeState>,
+    /// Tracks whether an update was skipped this frame because of the frame rate limit.
+    update_deferred: bool,
+    /// When the event loop was asked to wake up for the next frame, if the frame rate is
+    /// limited. Used to measure [`FRAME_PACING_JITTER`].
+    frame_target: Option<Instant>,
 }
 impl Default for TaoPersistentState {
     fn default() -> Self {
@@ -226,6 +254,28 @@ impl Default for TaoPersistentState {
             redraw_request_sent: false,
             timeout_reached: false,
             last_update: Instant::now(),
+            windows: HashMap::default(),
+            update_deferred: false,
+            frame_target: None,
+        }
+    }
+}
+
+/// The part of [`TaoPersistentState`] tracked separately for each window.
+struct WindowWakeState {
+    /// Tracks whether an event directly caused by interaction with this window occurred
+    /// since the last update. Should be reset after every update.
+    window_event: bool,
+    /// Tracks whether a [`RequestWindowRedraw`] for this window is still pending.
+    redraw_requested: bool,
+    last_update: Instant,
+}
+impl Default for WindowWakeState {
+    fn default() -> Self {
+        Self {
+            window_event: false,
+            redraw_requested: false,
+            last_update: Instant::now(),
         }
     }
 }
@@ -242,7 +292,9 @@ pub fn tao_runner<W: GetWindow + 'static>(mut app: App) {
 
     let mut app_exit_event_reader = ManualEventReader::<AppExit>::default();
     let mut redraw_event_reader = ManualEventReader::<RequestRedraw>::default();
+    let mut window_redraw_event_reader = ManualEventReader::<RequestWindowRedraw>::default();
     let mut tao_state = TaoPersistentState::default();
+    tao_state.active = true;
     app.world
         .insert_non_send_resource(event_loop.create_proxy());
 
@@ -257,6 +309,11 @@ pub fn tao_runner<W: GetWindow + 'static>(mut app: App) {
         NonSendMut<TaoWindows<W>>,
     )> = SystemState::from_world(&mut app.world);
 
+    let mut focused_window_state: SystemState<(
+        Res<TaoSettings>,
+        Query<(Entity, &Window, Option<&WindowUpdateMode>)>,
+    )> = SystemState::from_world(&mut app.world);
+
     let mut finished_and_setup_done = false;
 
     let event_handler = move |event: Event<()>,
@@ -285,14 +342,28 @@ pub fn tao_runner<W: GetWindow + 'static>(mut app: App) {
 
         match event {
             event::Event::NewEvents(start) => {
+                let (tao_config, window_focused_query) = focused_window_state.get(&app.world);
+
+                let app_focused = window_focused_query
+                    .iter()
+                    .any(|(_, window, _)| window.focused);
+
                 // Check if either the `WaitUntil` timeout was triggered by tao, or that same
                 // amount of time has elapsed since the last app update. This manual check is needed
                 // because we don't know if the criteria for an app update were met until the end of
                 // the frame.
                 let auto_timeout_reached = matches!(start, StartCause::ResumeTimeReached { .. });
+                let now = Instant::now();
+                let manual_timeout_reached = match tao_config.update_mode(app_focused) {
+                    UpdateMode::Continuous => false,
+                    UpdateMode::Reactive { max_wait }
+                    | UpdateMode::ReactiveLowPower { max_wait } => {
+                        now.duration_since(tao_state.last_update) >= *max_wait
+                    }
+                };
                 // The low_power_event state and timeout must be reset at the start of every frame.
                 tao_state.low_power_event = false;
-                tao_state.timeout_reached = auto_timeout_reached;
+                tao_state.timeout_reached = auto_timeout_reached || manual_timeout_reached;
             }
             event::Event::WindowEvent {
                 event,
@@ -341,6 +412,11 @@ pub fn tao_runner<W: GetWindow + 'static>(mut app: App) {
                     };
 
                 tao_state.low_power_event = true;
+                tao_state
+                    .windows
+                    .entry(window_entity)
+                    .or_default()
+                    .window_event = true;
 
                 match event {
                     WindowEvent::Resized(size) => {
@@ -579,13 +655,118 @@ pub fn tao_runner<W: GetWindow + 'static>(mut app: App) {
                 tao_state.active = true;
             }
             event::Event::MainEventsCleared => {
-                if finished_and_setup_done {
-                    tao_state.last_update = Instant::now();
+                let (tao_config, window_query) = focused_window_state.get(&app.world);
+                let now = Instant::now();
+
+                // A window needs an update if its own update mode says so, if a redraw was
+                // requested for it, or if its `max_wait` elapsed since it was last updated.
+                let mut windows_to_redraw = HashSet::default();
+                for (entity, window, update_mode) in &window_query {
+                    let window_state = tao_state.windows.entry(entity).or_default();
+                    let needs_update = match tao_config.window_update_mode(window, update_mode) {
+                        UpdateMode::Continuous | UpdateMode::Reactive { .. } => true,
+                        UpdateMode::ReactiveLowPower { max_wait } => {
+                            window_state.window_event
+                                || window_state.redraw_requested
+                                || now.duration_since(window_state.last_update) >= *max_wait
+                        }
+                    };
+                    if needs_update || tao_state.redraw_request_sent {
+                        windows_to_redraw.insert(entity);
+                    }
+                }
+                // Forget about windows that do not exist anymore
+                tao_state
+                    .windows
+                    .retain(|entity, _| window_query.contains(*entity));
+
+                let update = if !tao_state.active {
+                    false
+                } else if window_query.is_empty() {
+                    // Without windows, fall back to the app-wide update mode
+                    match tao_config.update_mode(false) {
+                        UpdateMode::Continuous | UpdateMode::Reactive { .. } => true,
+                        UpdateMode::ReactiveLowPower { .. } => {
+                            tao_state.low_power_event
+                                || tao_state.redraw_request_sent
+                                || tao_state.timeout_reached
+                        }
+                    }
+                } else {
+                    !windows_to_redraw.is_empty()
+                };
+
+                // Events received before the next frame is due are handled in the next update.
+                let app_focused = window_query.iter().any(|(_, window, _)| window.focused);
+                let too_early = tao_config
+                    .frame_duration(app_focused)
+                    .map_or(false, |frame| {
+                        now.duration_since(tao_state.last_update) < frame
+                    });
+                tao_state.update_deferred = update && too_early;
+
+                if update && !too_early && finished_and_setup_done {
+                    if let Some(frame_target) = tao_state.frame_target.take() {
+                        let jitter = now.saturating_duration_since(frame_target);
+                        record_frame_pacing_jitter(&mut app.world, now, jitter);
+                    }
+                    tao_state.last_update = now;
+                    for window_state in tao_state.windows.values_mut() {
+                        window_state.window_event = false;
+                    }
+                    for entity in &windows_to_redraw {
+                        let window_state = tao_state.windows.entry(*entity).or_default();
+                        window_state.last_update = now;
+                        window_state.redraw_requested = false;
+                    }
+                    app.world
+                        .insert_resource(WindowsToRedraw(windows_to_redraw));
                     app.update();
                 }
             }
             Event::RedrawEventsCleared => {
-                *control_flow = ControlFlow::Poll;
+                let frame_duration = {
+                    // Fetch from world
+                    let (tao_config, window_query) = focused_window_state.get(&app.world);
+
+                    // Each window's update mode, with the last time it was updated
+                    let mut update_modes: Vec<_> = window_query
+                        .iter()
+                        .map(|(entity, window, update_mode)| {
+                            let last_update = tao_state
+                                .windows
+                                .get(&entity)
+                                .map_or(tao_state.last_update, |state| state.last_update);
+                            let update_mode = tao_config.window_update_mode(window, update_mode);
+                            (update_mode, last_update)
+                        })
+                        .collect();
+                    if update_modes.is_empty() {
+                        update_modes.push((tao_config.update_mode(false), tao_state.last_update));
+                    }
+
+                    // Sleep until the earliest `max_wait` deadline of all windows.
+                    use UpdateMode::*;
+                    *control_flow = update_modes.into_iter().fold(
+                        ControlFlow::Wait,
+                        |control_flow, (update_mode, last_update)| match update_mode {
+                            Continuous => ControlFlow::Poll,
+                            Reactive { max_wait } | ReactiveLowPower { max_wait } => {
+                                match (control_flow, last_update.checked_add(*max_wait)) {
+                                    (ControlFlow::Poll, _) | (_, None) => control_flow,
+                                    (ControlFlow::WaitUntil(current), Some(instant)) => {
+                                        ControlFlow::WaitUntil(current.min(instant))
+                                    }
+                                    (_, Some(instant)) => ControlFlow::WaitUntil(instant),
+                                }
+                            }
+                        },
+                    );
+
+                    // True if _any_ windows are currently being focused
+                    let app_focused = window_query.iter().any(|(_, window, _)| window.focused);
+                    tao_config.frame_duration(app_focused)
+                };
 
                 // This block needs to run after `app.update()` in `MainEventsCleared`. Otherwise,
                 // we won't be able to see redraw requests until the next event, defeating the
@@ -598,7 +779,37 @@ pub fn tao_runner<W: GetWindow + 'static>(mut app: App) {
                     }
                 }
 
-                tao_state.redraw_request_sent = redraw;
+                // Keep the redraw request around if the update it should trigger was deferred.
+                tao_state.redraw_request_sent =
+                    redraw || (tao_state.update_deferred && tao_state.redraw_request_sent);
+
+                if let Some(window_redraw_events) =
+                    app.world.get_resource::<Events<RequestWindowRedraw>>()
+                {
+                    for event in window_redraw_event_reader.iter(window_redraw_events) {
+                        let window_state = tao_state.windows.entry(event.window).or_default();
+                        window_state.redraw_requested = true;
+                        *control_flow = ControlFlow::Poll;
+                    }
+                }
+
+                // Sleep until the next frame is due rather than spinning, if the frame
+                // rate is limited.
+                tao_state.frame_target = None;
+                if let Some(frame_duration) = frame_duration {
+                    let next_frame = tao_state.last_update + frame_duration;
+                    *control_flow = match *control_flow {
+                        _ if tao_state.update_deferred => ControlFlow::WaitUntil(next_frame),
+                        ControlFlow::Poll => ControlFlow::WaitUntil(next_frame),
+                        ControlFlow::WaitUntil(instant) => {
+                            ControlFlow::WaitUntil(instant.max(next_frame))
+                        }
+                        control_flow => control_flow,
+                    };
+                    if *control_flow == ControlFlow::WaitUntil(next_frame) {
+                        tao_state.frame_target = Some(next_frame);
+                    }
+                }
             }
 
             _ => (),
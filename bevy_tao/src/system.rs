@@ -1,17 +1,20 @@
 use bevy::ecs::{
     entity::Entity,
-    event::EventWriter,
-    prelude::{Changed, Component, Resource},
+    event::{EventReader, EventWriter},
+    prelude::{Changed, Component, Or, Resource},
     removal_detection::RemovedComponents,
     system::{Commands, NonSendMut, Query},
     world::Mut,
 };
+use bevy::input::{mouse::MouseButtonInput, ButtonState};
 use bevy::log;
 use bevy::utils::{
     tracing::{error, info, warn},
     HashMap,
 };
-use bevy::window::{RawHandleWrapper, Window, WindowClosed, WindowCreated};
+use bevy::window::{
+    CursorMoved, RawHandleWrapper, Window, WindowClosed, WindowCreated, WindowFocused,
+};
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 
 use tao::{
@@ -19,7 +22,7 @@ use tao::{
     event_loop::EventLoopWindowTarget,
 };
 
-use crate::GetWindow;
+use crate::{DragRequest, GetWindow, TaoWindow, WindowRequests};
 
 use super::{
     // accessibility::{AccessKitAdapters, WinitActionHandlers},
@@ -67,7 +70,8 @@ pub(crate) fn create_window<'a, W: GetWindow>(
             })
             .insert(CachedWindow {
                 window: window.clone(),
-            });
+            })
+            .insert(WindowRequests::default());
 
         event_writer.send(WindowCreated { window: entity });
     }
@@ -94,6 +98,46 @@ pub(crate) fn despawn_window<W: GetWindow>(
     }
 }
 
+/// Clear attention requests of windows that just got focused.
+pub(crate) fn clear_attention_on_focus(
+    mut focused: EventReader<WindowFocused>,
+    mut requests: Query<&mut WindowRequests>,
+) {
+    for event in focused.iter().filter(|event| event.focused) {
+        if let Ok(mut requests) = requests.get_mut(event.window) {
+            if requests.attention().is_some() {
+                requests.clear_attention();
+            }
+        }
+    }
+}
+
+/// Start the title bar moves of windows once the cursor moves, or cancel them when
+/// the button is released, see [`WindowRequests::title_bar_pressed`].
+pub(crate) fn pending_title_bar_moves(
+    mut cursor_moved: EventReader<CursorMoved>,
+    mut buttons: EventReader<MouseButtonInput>,
+    mut requests: Query<&mut WindowRequests>,
+) {
+    for event in buttons.iter() {
+        if event.state != ButtonState::Released {
+            continue;
+        }
+        if let Ok(mut requests) = requests.get_mut(event.window) {
+            if requests.move_pending() {
+                requests.button_released(event.button);
+            }
+        }
+    }
+    for event in cursor_moved.iter() {
+        if let Ok(mut requests) = requests.get_mut(event.window) {
+            if requests.move_pending() {
+                requests.cursor_moved(event.position);
+            }
+        }
+    }
+}
+
 /// The cached state of the window so we can check which properties were changed from within the app.
 #[derive(Debug, Clone, Component)]
 pub struct CachedWindow {
@@ -108,10 +152,18 @@ pub struct CachedWindow {
 // - [`Window::canvas`] currently cannot be updated after startup, not entirely sure if it would work well with the
 //   event channel stuff.
 pub(crate) fn changed_window<W: GetWindow>(
-    mut changed_windows: Query<(Entity, &mut Window, &mut CachedWindow), Changed<Window>>,
+    mut changed_windows: Query<
+        (
+            Entity,
+            &mut Window,
+            &mut CachedWindow,
+            Option<&mut WindowRequests>,
+        ),
+        Or<(Changed<Window>, Changed<WindowRequests>)>,
+    >,
     tao_windows: NonSendMut<TaoWindows<W>>,
 ) {
-    for (entity, mut window, mut cache) in &mut changed_windows {
+    for (entity, mut window, mut cache, mut requests) in &mut changed_windows {
         log::trace!("A window changed");
         if let Some(tao_window) = tao_windows.get_window(entity) {
             let tao_window = tao_window.get_window();
@@ -249,6 +301,18 @@ pub(crate) fn changed_window<W: GetWindow>(
                 tao_window.set_focus();
             }
 
+            // Bypass change detection, otherwise we would run this system on this window every frame.
+            let mut requests = requests.as_mut().map(|r| r.bypass_change_detection());
+            let drag_request = requests.as_mut().and_then(|r| r.take_drag_request());
+            if let Some(request) = drag_request {
+                start_drag(tao_window, request);
+            }
+
+            if let Some(attention) = requests.as_mut().and_then(|r| r.take_attention_request()) {
+                tao_window
+                    .request_user_attention(attention.map(converters::convert_user_attention));
+            }
+
             if window.window_level != cache.window.window_level {
                 set_window_level(window.window_level, tao_window);
             }
@@ -280,3 +344,56 @@ pub(crate) fn changed_window<W: GetWindow>(
         }
     }
 }
+
+/// Hand over an interactive move or resize to the window manager.
+///
+/// On linux, we go through GTK directly, so that the drag is tied to the button
+/// actually held down, and so that resizing is supported.
+#[cfg(target_os = "linux")]
+fn start_drag(tao_window: &TaoWindow, request: DragRequest) {
+    use gtk::prelude::{GtkWindowExt, WidgetExt};
+    use tao::platform::unix::WindowExtUnix;
+
+    let gtk_window = tao_window.gtk_window();
+    let pointer = gtk_window
+        .display()
+        .default_seat()
+        .and_then(|seat| seat.pointer());
+    let position = pointer.map(|pointer| {
+        let (_, x, y) = pointer.position();
+        (x, y)
+    });
+    let timestamp = gtk::current_event_time();
+    match (request, position) {
+        (DragRequest::Move { button }, Some((x, y))) => {
+            gtk_window.begin_move_drag(converters::convert_gtk_button(button), x, y, timestamp);
+        }
+        (DragRequest::Resize { edge, button }, Some((x, y))) => {
+            let edge = converters::convert_resize_edge(edge);
+            let button = converters::convert_gtk_button(button);
+            gtk_window.begin_resize_drag(edge, button, x, y, timestamp);
+        }
+        (DragRequest::ToggleMaximize, _) => {
+            tao_window.set_maximized(!tao_window.is_maximized());
+        }
+        (request, None) => {
+            warn!("Could not start {request:?}: no pointer device found");
+        }
+    }
+}
+#[cfg(not(target_os = "linux"))]
+fn start_drag(tao_window: &TaoWindow, request: DragRequest) {
+    match request {
+        DragRequest::Move { .. } => {
+            if let Err(err) = tao_window.drag_window() {
+                warn!("Could not start {request:?}: {err:?}");
+            }
+        }
+        DragRequest::Resize { .. } => {
+            warn!("tao doesn't support resizing windows from an edge on this platform");
+        }
+        DragRequest::ToggleMaximize => {
+            tao_window.set_maximized(!tao_window.is_maximized());
+        }
+    }
+}
@@ -1,22 +1,26 @@
-use bevy::ecs::system::Resource;
+use bevy::ecs::{entity::Entity, event::Event, prelude::Component, system::Resource};
+use bevy::utils::{Duration, HashSet};
+use bevy::window::Window;
 
-/// A resource for configuring usage of the [`winit`] library.
+use crate::frame_pacing;
+
+/// A resource for configuring usage of the [`tao`] library.
 #[derive(Debug, Resource)]
 pub struct TaoSettings {
-    /// Configures `winit` to return control to the caller after exiting the
+    /// Configures `tao` to return control to the caller after exiting the
     /// event loop, enabling [`App::run()`](bevy_app::App::run()) to return.
     ///
     /// By default, [`return_from_run`](Self::return_from_run) is `false` and *Bevy*
-    /// will use `winit`'s
-    /// [`EventLoop::run()`](https://docs.rs/winit/latest/winit/event_loop/struct.EventLoop.html#method.run)
+    /// will use `tao`'s
+    /// [`EventLoop::run()`](https://docs.rs/tao/latest/tao/event_loop/struct.EventLoop.html#method.run)
     /// to initiate the event loop.
-    /// [`EventLoop::run()`](https://docs.rs/winit/latest/winit/event_loop/struct.EventLoop.html#method.run)
+    /// [`EventLoop::run()`](https://docs.rs/tao/latest/tao/event_loop/struct.EventLoop.html#method.run)
     /// will never return but will terminate the process after the event loop exits.
     ///
     /// Setting [`return_from_run`](Self::return_from_run) to `true` will cause *Bevy*
-    /// to use `winit`'s
-    /// [`EventLoopExtRunReturn::run_return()`](https://docs.rs/winit/latest/winit/platform/run_return/trait.EventLoopExtRunReturn.html#tymethod.run_return)
-    /// instead which is strongly discouraged by the `winit` authors.
+    /// to use `tao`'s
+    /// [`EventLoopExtRunReturn::run_return()`](https://docs.rs/tao/latest/tao/platform/run_return/trait.EventLoopExtRunReturn.html#tymethod.run_return)
+    /// instead which is strongly discouraged by the `tao` authors.
     ///
     /// # Supported platforms
     ///
@@ -26,10 +30,68 @@ pub struct TaoSettings {
     /// Setting [`return_from_run`](Self::return_from_run) to `true` on
     /// unsupported platforms will cause [`App::run()`](bevy_app::App::run()) to panic!
     pub return_from_run: bool,
-    /// Configures how the winit event loop updates while the window is focused.
+    /// Configures how the tao event loop updates while the window is focused.
     pub focused_mode: UpdateMode,
-    /// Configures how the winit event loop updates while the window is *not* focused.
+    /// Configures how the tao event loop updates while the window is *not* focused.
     pub unfocused_mode: UpdateMode,
+    /// The maximum number of app updates per second while a window is focused,
+    /// `None` to update as often as the [`UpdateMode`] allows.
+    ///
+    /// Between frames, the runner sleeps until the next frame is due, see
+    /// [`FRAME_PACING_JITTER`](crate::FRAME_PACING_JITTER) to measure how precise this is.
+    pub frame_rate_limit: Option<f64>,
+    /// The maximum number of app updates per second while no window is focused.
+    ///
+    /// When `None`, [`frame_rate_limit`](Self::frame_rate_limit) is used instead.
+    pub background_frame_rate_limit: Option<f64>,
+}
+impl TaoSettings {
+    /// Configure tao with common settings for a game.
+    pub fn game() -> Self {
+        TaoSettings::default()
+    }
+
+    /// Configure tao with common settings for a desktop application.
+    pub fn desktop_app() -> Self {
+        TaoSettings {
+            focused_mode: UpdateMode::Reactive {
+                max_wait: Duration::from_secs(5),
+            },
+            unfocused_mode: UpdateMode::ReactiveLowPower {
+                max_wait: Duration::from_secs(60),
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Gets the configured [`UpdateMode`] depending on whether the window is focused or not
+    pub fn update_mode(&self, focused: bool) -> &UpdateMode {
+        match focused {
+            true => &self.focused_mode,
+            false => &self.unfocused_mode,
+        }
+    }
+
+    /// Gets the minimum time between two frames, depending on whether a window is focused or not.
+    pub fn frame_duration(&self, focused: bool) -> Option<Duration> {
+        let limit = match focused {
+            true => self.frame_rate_limit,
+            false => self.background_frame_rate_limit.or(self.frame_rate_limit),
+        };
+        frame_pacing::frame_duration(limit)
+    }
+
+    /// Gets the [`UpdateMode`] of `window`, using its [`WindowUpdateMode`] if it has one.
+    pub fn window_update_mode<'a>(
+        &'a self,
+        window: &Window,
+        window_mode: Option<&'a WindowUpdateMode>,
+    ) -> &'a UpdateMode {
+        match window_mode {
+            Some(window_mode) => window_mode.update_mode(window.focused),
+            None => self.update_mode(window.focused),
+        }
+    }
 }
 impl Default for TaoSettings {
     fn default() -> Self {
@@ -37,13 +99,109 @@ impl Default for TaoSettings {
             return_from_run: false,
             focused_mode: UpdateMode::Continuous,
             unfocused_mode: UpdateMode::Continuous,
+            frame_rate_limit: None,
+            background_frame_rate_limit: None,
         }
     }
 }
 
-/// Configure how the winit event loop should update.
+/// Configure how the tao event loop should update.
 #[derive(Debug)]
 pub enum UpdateMode {
     /// The event loop will update continuously, running as fast as possible.
     Continuous,
+    /// The event loop will only update if there is a tao event, a redraw is requested, or the
+    /// maximum wait time has elapsed.
+    ///
+    /// ## Note
+    ///
+    /// Once the app has executed all bevy systems and reaches the end of the event loop, there is
+    /// no way to force the app to wake and update again, unless a `tao` event (such as user
+    /// input, or the window being resized) is received or the time limit is reached.
+    Reactive {
+        /// The maximum time to wait before the event loop runs again.
+        ///
+        /// Note that Bevy will wait indefinitely if the duration is too high (such as [`Duration::MAX`]).
+        max_wait: Duration,
+    },
+    /// The event loop will only update if there is a tao event from direct interaction with the
+    /// window (e.g. mouseover), a redraw is requested, or the maximum wait time has elapsed.
+    ///
+    /// ## Note
+    ///
+    /// Once the app has executed all bevy systems and reaches the end of the event loop, there is
+    /// no way to force the app to wake and update again, unless a `tao` event (such as user
+    /// input, or the window being resized) is received or the time limit is reached.
+    ///
+    /// ## Differences from [`UpdateMode::Reactive`]
+    ///
+    /// Unlike [`UpdateMode::Reactive`], this mode will ignore tao events that aren't directly
+    /// caused by interaction with the window. For example, you might want to use this mode when the
+    /// window is not focused, to only re-draw your bevy app when the cursor is over the window, but
+    /// not when the mouse moves somewhere else on the screen. This helps to significantly reduce
+    /// power consumption by only updated the app when absolutely necessary.
+    ReactiveLowPower {
+        /// The maximum time to wait before the event loop runs again.
+        ///
+        /// Note that Bevy will wait indefinitely if the duration is too high (such as [`Duration::MAX`]).
+        max_wait: Duration,
+    },
+}
+
+/// Per-window override of the [`TaoSettings`] update modes.
+///
+/// Add this to a window entity so that it is updated according to its own focus state,
+/// independently of other windows. For example, a static inspector window could use
+/// [`UpdateMode::ReactiveLowPower`] to only be redrawn when interacted with,
+/// while the main viewport is [`UpdateMode::Continuous`].
+#[derive(Debug, Component)]
+pub struct WindowUpdateMode {
+    /// Configures how the tao event loop updates while this window is focused.
+    pub focused_mode: UpdateMode,
+    /// Configures how the tao event loop updates while this window is *not* focused.
+    pub unfocused_mode: UpdateMode,
+}
+impl WindowUpdateMode {
+    /// Update this window only when interacted with, or when a redraw is requested.
+    pub fn on_demand(max_wait: Duration) -> Self {
+        WindowUpdateMode {
+            focused_mode: UpdateMode::ReactiveLowPower { max_wait },
+            unfocused_mode: UpdateMode::ReactiveLowPower { max_wait },
+        }
+    }
+
+    /// Gets the configured [`UpdateMode`] depending on whether the window is focused or not
+    pub fn update_mode(&self, focused: bool) -> &UpdateMode {
+        match focused {
+            true => &self.focused_mode,
+            false => &self.unfocused_mode,
+        }
+    }
+}
+
+/// Request a redraw of a single window.
+///
+/// Unlike [`RequestRedraw`](bevy::window::RequestRedraw), this only wakes up `window`
+/// if it uses [`UpdateMode::ReactiveLowPower`].
+#[derive(Debug, Clone, Copy, Event)]
+pub struct RequestWindowRedraw {
+    /// The window to redraw.
+    pub window: Entity,
+}
+
+/// The windows that need to be redrawn during the current app update.
+///
+/// Set by the runner before each update. A window is in this set if its [`UpdateMode`]
+/// (or [`WindowUpdateMode`]) caused the update, or if a redraw was requested for it.
+///
+/// With the `render` feature, the renderer skips the other windows, which keep showing
+/// their last frame, unless their [`Window`] changed. Use this to also skip expensive
+/// per-window work on windows that didn't change.
+#[derive(Debug, Default, Resource)]
+pub struct WindowsToRedraw(pub HashSet<Entity>);
+impl WindowsToRedraw {
+    /// Whether `window` needs to be redrawn this update.
+    pub fn contains(&self, window: Entity) -> bool {
+        self.0.contains(&window)
+    }
 }
@@ -0,0 +1,241 @@
+// Keep in sync with `bevy_winit_gtk/src/window_requests.rs`: both backends expose the same API.
+
+use bevy::ecs::prelude::Component;
+use bevy::input::mouse::MouseButton;
+use bevy::math::Vec2;
+use bevy::utils::{Duration, Instant};
+
+/// Maximum delay between two title bar presses for them to count as a double-click.
+pub const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Distance the cursor moves, in logical pixels, before a title bar press starts a move.
+pub const DRAG_THRESHOLD: f32 = 4.0;
+
+/// An edge or corner of a window, used to start an interactive resize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResizeEdge {
+    /// The top edge.
+    North,
+    /// The bottom edge.
+    South,
+    /// The right edge.
+    East,
+    /// The left edge.
+    West,
+    /// The top right corner.
+    NorthEast,
+    /// The top left corner.
+    NorthWest,
+    /// The bottom right corner.
+    SouthEast,
+    /// The bottom left corner.
+    SouthWest,
+}
+
+/// An interactive move or resize of the window, handed over to the window manager.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DragRequest {
+    /// Move the window with the cursor, as if the title bar was dragged.
+    Move {
+        /// The mouse button currently held down.
+        button: MouseButton,
+    },
+    /// Resize the window from the given edge, as if the window border was dragged.
+    Resize {
+        /// The edge or corner being dragged.
+        edge: ResizeEdge,
+        /// The mouse button currently held down.
+        button: MouseButton,
+    },
+    /// Maximize the window if it isn't already, restore it otherwise.
+    ToggleMaximize,
+}
+
+/// How urgently the window asks for the user's attention, see [`WindowRequests::request_attention`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UserAttention {
+    /// Flash the taskbar entry until the window is focused, or similar depending on the platform.
+    Critical,
+    /// Flash the taskbar entry once, or similar depending on the platform.
+    Informational,
+}
+
+/// Commands for a window that are not expressed as state on the [`Window`] component.
+///
+/// This is mostly useful for windows with `decorations: false`, where the app draws its own
+/// title bar and needs to hand over moving and resizing to the window manager.
+///
+/// This is added to all window entities when the native window is created.
+/// Requests are applied in `changed_window` and cleared once handled.
+///
+/// [`Window`]: bevy::window::Window
+#[derive(Component, Debug, Default, Clone)]
+pub struct WindowRequests {
+    drag: Option<DragRequest>,
+    last_title_bar_press: Option<Instant>,
+    attention: Option<UserAttention>,
+    attention_changed: bool,
+    /// The button of a title bar press waiting for the cursor to move, and where the
+    /// cursor was first seen since.
+    pending_move: Option<(MouseButton, Option<Vec2>)>,
+}
+impl WindowRequests {
+    /// Start moving the window with the cursor.
+    ///
+    /// `button` must be currently pressed, the move ends when it is released.
+    pub fn start_drag(&mut self, button: MouseButton) {
+        self.drag = Some(DragRequest::Move { button });
+    }
+
+    /// Start resizing the window from `edge`.
+    ///
+    /// `button` must be currently pressed, the resize ends when it is released.
+    pub fn start_resize(&mut self, edge: ResizeEdge, button: MouseButton) {
+        self.drag = Some(DragRequest::Resize { edge, button });
+    }
+
+    /// Maximize the window, or restore it if it is already maximized.
+    pub fn toggle_maximize(&mut self) {
+        self.drag = Some(DragRequest::ToggleMaximize);
+    }
+
+    /// Handle a press of `button` on an app-drawn title bar.
+    ///
+    /// A second press within [`DOUBLE_CLICK_INTERVAL`] toggles maximization, otherwise
+    /// the window starts moving once the cursor moves by [`DRAG_THRESHOLD`] with
+    /// `button` held. The window manager grabs the pointer during a move, so starting
+    /// it right away would hide the second press of a double-click from the app.
+    pub fn title_bar_pressed(&mut self, button: MouseButton) {
+        self.title_bar_pressed_at(button, Instant::now());
+    }
+
+    fn title_bar_pressed_at(&mut self, button: MouseButton, now: Instant) {
+        if is_double_click(self.last_title_bar_press, now) {
+            self.last_title_bar_press = None;
+            self.pending_move = None;
+            self.toggle_maximize();
+        } else {
+            self.last_title_bar_press = Some(now);
+            self.pending_move = Some((button, None));
+        }
+    }
+
+    /// Whether a title bar press is waiting for the cursor to move.
+    pub(crate) fn move_pending(&self) -> bool {
+        self.pending_move.is_some()
+    }
+
+    /// Start the pending title bar move if the cursor moved far enough.
+    pub(crate) fn cursor_moved(&mut self, position: Vec2) {
+        let Some((button, origin)) = &mut self.pending_move else {
+            return;
+        };
+        let origin = *origin.get_or_insert(position);
+        if origin.distance(position) >= DRAG_THRESHOLD {
+            let button = *button;
+            self.pending_move = None;
+            self.start_drag(button);
+        }
+    }
+
+    /// Ask for the user's attention, typically by flashing the taskbar entry.
+    ///
+    /// This is useful to notify the user when something happens while the window is
+    /// in the background. The request is cleared when the window gets focused.
+    pub fn request_attention(&mut self, attention: UserAttention) {
+        self.attention = Some(attention);
+        self.attention_changed = true;
+    }
+
+    /// Stop asking for the user's attention.
+    pub fn clear_attention(&mut self) {
+        self.attention = None;
+        self.attention_changed = true;
+    }
+
+    /// The current attention request of this window, if any.
+    pub fn attention(&self) -> Option<UserAttention> {
+        self.attention
+    }
+
+    /// Consume the pending attention change, if any.
+    ///
+    /// Returns `Some(None)` if the attention request was cleared.
+    pub fn take_attention_request(&mut self) -> Option<Option<UserAttention>> {
+        std::mem::take(&mut self.attention_changed).then_some(self.attention)
+    }
+
+    /// Cancel the pending title bar move of `button`.
+    pub(crate) fn button_released(&mut self, button: MouseButton) {
+        if matches!(self.pending_move, Some((pending, _)) if pending == button) {
+            self.pending_move = None;
+        }
+    }
+
+    /// Consume the pending drag request, if any.
+    pub fn take_drag_request(&mut self) -> Option<DragRequest> {
+        self.drag.take()
+    }
+}
+
+/// Whether a title bar press at `now` completes a double-click started at `last`.
+fn is_double_click(last: Option<Instant>, now: Instant) -> bool {
+    last.map_or(false, |last| {
+        now.duration_since(last) <= DOUBLE_CLICK_INTERVAL
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_click_timing() {
+        let now = Instant::now();
+        assert!(!is_double_click(None, now));
+        assert!(is_double_click(Some(now), now + DOUBLE_CLICK_INTERVAL));
+        let late = now + DOUBLE_CLICK_INTERVAL + Duration::from_millis(1);
+        assert!(!is_double_click(Some(now), late));
+    }
+
+    #[test]
+    fn title_bar_double_click_toggles_maximize() {
+        let now = Instant::now();
+        let mut requests = WindowRequests::default();
+        requests.title_bar_pressed_at(MouseButton::Left, now);
+        assert_eq!(requests.take_drag_request(), None);
+        requests.button_released(MouseButton::Left);
+        requests.title_bar_pressed_at(MouseButton::Left, now + Duration::from_millis(200));
+        assert_eq!(
+            requests.take_drag_request(),
+            Some(DragRequest::ToggleMaximize)
+        );
+        assert!(!requests.move_pending());
+    }
+
+    #[test]
+    fn title_bar_press_moves_after_threshold() {
+        let mut requests = WindowRequests::default();
+        requests.title_bar_pressed(MouseButton::Left);
+        requests.cursor_moved(Vec2::new(10.0, 10.0));
+        requests.cursor_moved(Vec2::new(11.0, 11.0));
+        assert_eq!(requests.take_drag_request(), None);
+        requests.cursor_moved(Vec2::new(14.0, 10.0));
+        assert_eq!(
+            requests.take_drag_request(),
+            Some(DragRequest::Move {
+                button: MouseButton::Left
+            })
+        );
+    }
+
+    #[test]
+    fn title_bar_release_cancels_move() {
+        let mut requests = WindowRequests::default();
+        requests.title_bar_pressed(MouseButton::Left);
+        requests.button_released(MouseButton::Left);
+        requests.cursor_moved(Vec2::ZERO);
+        requests.cursor_moved(Vec2::new(100.0, 0.0));
+        assert_eq!(requests.take_drag_request(), None);
+    }
+}
@@ -8,6 +8,8 @@ use bevy::input::{
 use bevy::math::Vec2;
 use bevy::window::{CursorIcon, WindowLevel, WindowTheme};
 
+use crate::{ResizeEdge, UserAttention};
+
 pub fn convert_keyboard_input(
     keyboard_input: &winit::event::KeyboardInput,
     window: Entity,
@@ -293,3 +295,50 @@ pub fn convert_window_theme(theme: WindowTheme) -> winit::window::Theme {
         WindowTheme::Dark => winit::window::Theme::Dark,
     }
 }
+
+#[cfg(not(all(target_os = "linux", feature = "winit-gtk")))]
+pub fn convert_resize_edge(edge: ResizeEdge) -> winit::window::ResizeDirection {
+    match edge {
+        ResizeEdge::North => winit::window::ResizeDirection::North,
+        ResizeEdge::South => winit::window::ResizeDirection::South,
+        ResizeEdge::East => winit::window::ResizeDirection::East,
+        ResizeEdge::West => winit::window::ResizeDirection::West,
+        ResizeEdge::NorthEast => winit::window::ResizeDirection::NorthEast,
+        ResizeEdge::NorthWest => winit::window::ResizeDirection::NorthWest,
+        ResizeEdge::SouthEast => winit::window::ResizeDirection::SouthEast,
+        ResizeEdge::SouthWest => winit::window::ResizeDirection::SouthWest,
+    }
+}
+
+pub fn convert_user_attention(attention: UserAttention) -> winit::window::UserAttentionType {
+    match attention {
+        UserAttention::Critical => winit::window::UserAttentionType::Critical,
+        UserAttention::Informational => winit::window::UserAttentionType::Informational,
+    }
+}
+
+/// The GDK edge of a [`ResizeEdge`].
+#[cfg(all(target_os = "linux", feature = "winit-gtk"))]
+pub fn convert_gtk_resize_edge(edge: ResizeEdge) -> gtk::gdk::WindowEdge {
+    match edge {
+        ResizeEdge::North => gtk::gdk::WindowEdge::North,
+        ResizeEdge::South => gtk::gdk::WindowEdge::South,
+        ResizeEdge::East => gtk::gdk::WindowEdge::East,
+        ResizeEdge::West => gtk::gdk::WindowEdge::West,
+        ResizeEdge::NorthEast => gtk::gdk::WindowEdge::NorthEast,
+        ResizeEdge::NorthWest => gtk::gdk::WindowEdge::NorthWest,
+        ResizeEdge::SouthEast => gtk::gdk::WindowEdge::SouthEast,
+        ResizeEdge::SouthWest => gtk::gdk::WindowEdge::SouthWest,
+    }
+}
+
+/// The X11/GDK button number for a bevy mouse button.
+#[cfg(all(target_os = "linux", feature = "winit-gtk"))]
+pub fn convert_gtk_button(button: MouseButton) -> i32 {
+    match button {
+        MouseButton::Left => 1,
+        MouseButton::Middle => 2,
+        MouseButton::Right => 3,
+        MouseButton::Other(button) => i32::from(button),
+    }
+}
@@ -9,10 +9,14 @@
 
 #[cfg(feature = "accessibility")]
 pub mod accessibility;
+#[cfg(feature = "render")]
+mod clean_windows;
 mod converters;
+mod frame_pacing;
 mod system;
 #[cfg(target_arch = "wasm32")]
 mod web_resize;
+mod window_requests;
 mod winit_config;
 mod winit_windows;
 
@@ -21,12 +25,19 @@ use bevy::a11y::AccessibilityRequested;
 use bevy::ecs::system::{SystemParam, SystemState};
 #[cfg(not(target_arch = "wasm32"))]
 use bevy::tasks::tick_global_task_pools_on_main_thread;
-use system::{changed_window, create_window, despawn_window, CachedWindow};
+use frame_pacing::record_frame_pacing_jitter;
+use system::{
+    changed_window, clear_attention_on_focus, create_window, despawn_window,
+    pending_title_bar_moves, CachedWindow,
+};
 
+pub use frame_pacing::FRAME_PACING_JITTER;
+pub use window_requests::*;
 pub use winit_config::*;
 pub use winit_windows::*;
 
 use bevy::app::{App, AppExit, Last, Plugin};
+use bevy::diagnostic::{Diagnostic, RegisterDiagnostic};
 use bevy::ecs::event::{Events, ManualEventReader};
 use bevy::ecs::prelude::*;
 use bevy::input::{
@@ -38,7 +49,7 @@ use bevy::input::{
 use bevy::math::{ivec2, DVec2, Vec2};
 use bevy::utils::{
     tracing::{trace, warn},
-    Instant,
+    HashMap, HashSet, Instant,
 };
 use bevy::window::{
     exit_on_all_closed, CursorEntered, CursorLeft, CursorMoved, FileDragAndDrop, Ime,
@@ -112,13 +123,21 @@ impl Plugin for WinitPlugin {
         let event_loop = event_loop_builder.build();
 
         app.init_non_send_resource::<WinitWindows>()
+            .init_non_send_resource::<EventLoopTasks>()
             .init_resource::<WinitSettings>()
+            .init_resource::<WindowsToRedraw>()
+            .add_event::<RequestWindowRedraw>()
+            .register_diagnostic(
+                Diagnostic::new(FRAME_PACING_JITTER, "frame_pacing_jitter", 20).with_suffix("ms"),
+            )
             .set_runner(winit_runner)
             // exit_on_all_closed only uses the query to determine if the query is empty,
             // and so doesn't care about ordering relative to changed_window
             .add_systems(
                 Last,
                 (
+                    clear_attention_on_focus.before(changed_window),
+                    pending_title_bar_moves.before(changed_window),
                     changed_window.ambiguous_with(exit_on_all_closed),
                     // Update the state of the window before attempting to despawn to ensure consistent event ordering
                     despawn_window.after(changed_window),
@@ -128,6 +147,9 @@ impl Plugin for WinitPlugin {
         #[cfg(feature = "accessibility")]
         app.add_plugins(AccessibilityPlugin);
 
+        #[cfg(feature = "render")]
+        app.add_plugins(clean_windows::CleanWindowsPlugin);
+
         #[cfg(target_arch = "wasm32")]
         app.add_plugins(CanvasParentResizePlugin);
 
@@ -249,6 +271,15 @@ struct WinitPersistentState {
     /// timeout.
     timeout_reached: bool,
     last_update: Instant,
+    /// Per-window state, used to only update the app when a window needs it.
+    windows: HashMap<Entity, WindowWakeState>,
+    /// Tracks whether an update was skipped this frame because of the frame rate limit.
+    update_deferred: bool,
+    /// When the event loop was asked to wake up for the next frame, if the frame rate is
+    /// limited. Used to measure [`FRAME_PACING_JITTER`].
+    frame_target: Option<Instant>,
+    /// Windows that gained or lost focus since the last update, see [`flush_focus_changes`].
+    focus_changed: HashSet<Entity>,
 }
 impl Default for WinitPersistentState {
     fn default() -> Self {
@@ -258,6 +289,60 @@ impl Default for WinitPersistentState {
             redraw_request_sent: false,
             timeout_reached: false,
             last_update: Instant::now(),
+            windows: HashMap::default(),
+            update_deferred: false,
+            frame_target: None,
+            focus_changed: HashSet::default(),
+        }
+    }
+}
+
+/// The part of [`WinitPersistentState`] tracked separately for each window.
+#[derive(Debug)]
+struct WindowWakeState {
+    /// Tracks whether an event directly caused by interaction with this window occurred
+    /// since the last update. Should be reset after every update.
+    window_event: bool,
+    /// Tracks whether a [`RequestWindowRedraw`] for this window is still pending.
+    redraw_requested: bool,
+    last_update: Instant,
+}
+impl Default for WindowWakeState {
+    fn default() -> Self {
+        Self {
+            window_event: false,
+            redraw_requested: false,
+            last_update: Instant::now(),
+        }
+    }
+}
+
+/// Update [`Window::focused`] and send [`WindowFocused`] for windows whose focus changed
+/// since the last update.
+///
+/// A window counts as focused if it, or a window attached to it, has focus. This runs
+/// once all events are in, so that focus moving between a window and a window attached
+/// to it doesn't register as the window losing focus.
+fn flush_focus_changes(world: &mut World, changed: &mut HashSet<Entity>) {
+    let mut system_state: SystemState<(
+        NonSend<WinitWindows>,
+        Query<(&mut Window, &mut CachedWindow)>,
+        EventWriter<WindowFocused>,
+    )> = SystemState::new(world);
+    let (winit_windows, mut windows, mut focused_events) = system_state.get_mut(world);
+    for entity in changed.drain() {
+        let Ok((mut window, mut cache)) = windows.get_mut(entity) else {
+            continue;
+        };
+        let focused = winit_windows.is_entity_focused(entity);
+        if window.focused != focused {
+            window.focused = focused;
+            // The window manager already moved focus, `changed_window` must not move it again.
+            cache.window.focused = focused;
+            focused_events.send(WindowFocused {
+                window: entity,
+                focused,
+            });
         }
     }
 }
@@ -274,6 +359,7 @@ pub fn winit_runner(mut app: App) {
 
     let mut app_exit_event_reader = ManualEventReader::<AppExit>::default();
     let mut redraw_event_reader = ManualEventReader::<RequestRedraw>::default();
+    let mut window_redraw_event_reader = ManualEventReader::<RequestWindowRedraw>::default();
     let mut winit_state = WinitPersistentState::default();
     winit_state.active = true;
     app.world
@@ -283,8 +369,10 @@ pub fn winit_runner(mut app: App) {
 
     trace!("Entering winit event loop");
 
-    let mut focused_window_state: SystemState<(Res<WinitSettings>, Query<&Window>)> =
-        SystemState::from_world(&mut app.world);
+    let mut focused_window_state: SystemState<(
+        Res<WinitSettings>,
+        Query<(Entity, &Window, Option<&WindowUpdateMode>)>,
+    )> = SystemState::from_world(&mut app.world);
 
     let mut create_window_system_state =
         SystemState::<FullWindowParams>::from_world(&mut app.world);
@@ -319,7 +407,9 @@ pub fn winit_runner(mut app: App) {
             event::Event::NewEvents(start) => {
                 let (winit_config, window_focused_query) = focused_window_state.get(&app.world);
 
-                let app_focused = window_focused_query.iter().any(|window| window.focused);
+                let app_focused = window_focused_query
+                    .iter()
+                    .any(|(_, window, _)| window.focused);
 
                 // Check if either the `WaitUntil` timeout was triggered by winit, or that same
                 // amount of time has elapsed since the last app update. This manual check is needed
@@ -345,7 +435,7 @@ pub fn winit_runner(mut app: App) {
             } => {
                 // Fetch and prepare details from the world
                 let mut system_state: SystemState<(
-                    NonSend<WinitWindows>,
+                    NonSendMut<WinitWindows>,
                     Query<(&mut Window, &mut CachedWindow)>,
                     WindowEvents,
                     InputEvents,
@@ -353,7 +443,7 @@ pub fn winit_runner(mut app: App) {
                     EventWriter<FileDragAndDrop>,
                 )> = SystemState::new(&mut app.world);
                 let (
-                    winit_windows,
+                    mut winit_windows,
                     mut window_query,
                     mut window_events,
                     mut input_events,
@@ -362,16 +452,33 @@ pub fn winit_runner(mut app: App) {
                 ) = system_state.get_mut(&mut app.world);
 
                 // Entity of this window
-                let window_entity =
-                    if let Some(entity) = winit_windows.get_window_entity(winit_window_id) {
-                        entity
-                    } else {
-                        warn!(
-                            "Skipped event {:?} for unknown winit Window Id {:?}",
-                            event, winit_window_id
-                        );
-                        return;
-                    };
+                let window_entity = if let Some(entity) =
+                    winit_windows.get_window_entity(winit_window_id)
+                {
+                    entity
+                } else if let Some(host) = winit_windows.get_attached_window_host(winit_window_id) {
+                    match event {
+                        WindowEvent::CloseRequested => {
+                            window_events
+                                .window_close_requested
+                                .send(WindowCloseRequested { window: host });
+                        }
+                        WindowEvent::Focused(focused) => {
+                            winit_windows.set_focused(winit_window_id, focused);
+                            winit_state.focus_changed.insert(host);
+                            winit_state.low_power_event = true;
+                            winit_state.windows.entry(host).or_default().window_event = true;
+                        }
+                        _ => {}
+                    }
+                    return;
+                } else {
+                    warn!(
+                        "Skipped event {:?} for unknown winit Window Id {:?}",
+                        event, winit_window_id
+                    );
+                    return;
+                };
 
                 let (mut window, mut cache) =
                     if let Ok((window, info)) = window_query.get_mut(window_entity) {
@@ -385,6 +492,11 @@ pub fn winit_runner(mut app: App) {
                     };
 
                 winit_state.low_power_event = true;
+                winit_state
+                    .windows
+                    .entry(window_entity)
+                    .or_default()
+                    .window_event = true;
 
                 match event {
                     WindowEvent::Resized(size) => {
@@ -534,13 +646,8 @@ pub fn winit_runner(mut app: App) {
                             .set_physical_resolution(new_inner_size.width, new_inner_size.height);
                     }
                     WindowEvent::Focused(focused) => {
-                        // Component
-                        window.focused = focused;
-
-                        window_events.window_focused.send(WindowFocused {
-                            window: window_entity,
-                            focused,
-                        });
+                        winit_windows.set_focused(winit_window_id, focused);
+                        winit_state.focus_changed.insert(window_entity);
                     }
                     WindowEvent::DroppedFile(path_buf) => {
                         file_drag_and_drop_events.send(FileDragAndDrop::DroppedFile {
@@ -632,12 +739,39 @@ pub fn winit_runner(mut app: App) {
                 winit_state.active = true;
             }
             event::Event::MainEventsCleared => {
-                let (winit_config, window_focused_query) = focused_window_state.get(&app.world);
+                if !winit_state.focus_changed.is_empty() {
+                    flush_focus_changes(&mut app.world, &mut winit_state.focus_changed);
+                }
+                let (winit_config, window_query) = focused_window_state.get(&app.world);
+                let now = Instant::now();
 
-                let update = if winit_state.active {
-                    // True if _any_ windows are currently being focused
-                    let app_focused = window_focused_query.iter().any(|window| window.focused);
-                    match winit_config.update_mode(app_focused) {
+                // A window needs an update if its own update mode says so, if a redraw was
+                // requested for it, or if its `max_wait` elapsed since it was last updated.
+                let mut windows_to_redraw = HashSet::default();
+                for (entity, window, update_mode) in &window_query {
+                    let window_state = winit_state.windows.entry(entity).or_default();
+                    let needs_update = match winit_config.window_update_mode(window, update_mode) {
+                        UpdateMode::Continuous | UpdateMode::Reactive { .. } => true,
+                        UpdateMode::ReactiveLowPower { max_wait } => {
+                            window_state.window_event
+                                || window_state.redraw_requested
+                                || now.duration_since(window_state.last_update) >= *max_wait
+                        }
+                    };
+                    if needs_update || winit_state.redraw_request_sent {
+                        windows_to_redraw.insert(entity);
+                    }
+                }
+                // Forget about windows that do not exist anymore
+                winit_state
+                    .windows
+                    .retain(|entity, _| window_query.contains(*entity));
+
+                let update = if !winit_state.active {
+                    false
+                } else if window_query.is_empty() {
+                    // Without windows, fall back to the app-wide update mode
+                    match winit_config.update_mode(false) {
                         UpdateMode::Continuous | UpdateMode::Reactive { .. } => true,
                         UpdateMode::ReactiveLowPower { .. } => {
                             winit_state.low_power_event
@@ -646,35 +780,81 @@ pub fn winit_runner(mut app: App) {
                         }
                     }
                 } else {
-                    false
+                    !windows_to_redraw.is_empty()
                 };
 
-                if update && finished_and_setup_done {
-                    winit_state.last_update = Instant::now();
+                // Events received before the next frame is due are handled in the next update.
+                let app_focused = window_query.iter().any(|(_, window, _)| window.focused);
+                let too_early = winit_config
+                    .frame_duration(app_focused)
+                    .map_or(false, |frame| {
+                        now.duration_since(winit_state.last_update) < frame
+                    });
+                winit_state.update_deferred = update && too_early;
+
+                if update && !too_early && finished_and_setup_done {
+                    if let Some(frame_target) = winit_state.frame_target.take() {
+                        let jitter = now.saturating_duration_since(frame_target);
+                        record_frame_pacing_jitter(&mut app.world, now, jitter);
+                    }
+                    winit_state.last_update = now;
+                    for window_state in winit_state.windows.values_mut() {
+                        window_state.window_event = false;
+                    }
+                    for entity in &windows_to_redraw {
+                        let window_state = winit_state.windows.entry(*entity).or_default();
+                        window_state.last_update = now;
+                        window_state.redraw_requested = false;
+                    }
+                    app.world
+                        .insert_resource(WindowsToRedraw(windows_to_redraw));
                     app.update();
                 }
             }
             Event::RedrawEventsCleared => {
-                {
+                let frame_duration = {
                     // Fetch from world
-                    let (winit_config, window_focused_query) = focused_window_state.get(&app.world);
-
-                    // True if _any_ windows are currently being focused
-                    let app_focused = window_focused_query.iter().any(|window| window.focused);
+                    let (winit_config, window_query) = focused_window_state.get(&app.world);
+
+                    // Each window's update mode, with the last time it was updated
+                    let mut update_modes: Vec<_> = window_query
+                        .iter()
+                        .map(|(entity, window, update_mode)| {
+                            let last_update = winit_state
+                                .windows
+                                .get(&entity)
+                                .map_or(winit_state.last_update, |state| state.last_update);
+                            let update_mode = winit_config.window_update_mode(window, update_mode);
+                            (update_mode, last_update)
+                        })
+                        .collect();
+                    if update_modes.is_empty() {
+                        update_modes
+                            .push((winit_config.update_mode(false), winit_state.last_update));
+                    }
 
-                    let now = Instant::now();
+                    // Sleep until the earliest `max_wait` deadline of all windows.
                     use UpdateMode::*;
-                    *control_flow = match winit_config.update_mode(app_focused) {
-                        Continuous => ControlFlow::Poll,
-                        Reactive { max_wait } | ReactiveLowPower { max_wait } => {
-                            if let Some(instant) = now.checked_add(*max_wait) {
-                                ControlFlow::WaitUntil(instant)
-                            } else {
-                                ControlFlow::Wait
+                    *control_flow = update_modes.into_iter().fold(
+                        ControlFlow::Wait,
+                        |control_flow, (update_mode, last_update)| match update_mode {
+                            Continuous => ControlFlow::Poll,
+                            Reactive { max_wait } | ReactiveLowPower { max_wait } => {
+                                match (control_flow, last_update.checked_add(*max_wait)) {
+                                    (ControlFlow::Poll, _) | (_, None) => control_flow,
+                                    (ControlFlow::WaitUntil(current), Some(instant)) => {
+                                        ControlFlow::WaitUntil(current.min(instant))
+                                    }
+                                    (_, Some(instant)) => ControlFlow::WaitUntil(instant),
+                                }
                             }
-                        }
-                    };
-                }
+                        },
+                    );
+
+                    // True if _any_ windows are currently being focused
+                    let app_focused = window_query.iter().any(|(_, window, _)| window.focused);
+                    winit_config.frame_duration(app_focused)
+                };
 
                 // This block needs to run after `app.update()` in `MainEventsCleared`. Otherwise,
                 // we won't be able to see redraw requests until the next event, defeating the
@@ -687,7 +867,37 @@ pub fn winit_runner(mut app: App) {
                     }
                 }
 
-                winit_state.redraw_request_sent = redraw;
+                // Keep the redraw request around if the update it should trigger was deferred.
+                winit_state.redraw_request_sent =
+                    redraw || (winit_state.update_deferred && winit_state.redraw_request_sent);
+
+                if let Some(window_redraw_events) =
+                    app.world.get_resource::<Events<RequestWindowRedraw>>()
+                {
+                    for event in window_redraw_event_reader.iter(window_redraw_events) {
+                        let window_state = winit_state.windows.entry(event.window).or_default();
+                        window_state.redraw_requested = true;
+                        *control_flow = ControlFlow::Poll;
+                    }
+                }
+
+                // Sleep until the next frame is due rather than spinning, if the frame
+                // rate is limited.
+                winit_state.frame_target = None;
+                if let Some(frame_duration) = frame_duration {
+                    let next_frame = winit_state.last_update + frame_duration;
+                    *control_flow = match *control_flow {
+                        _ if winit_state.update_deferred => ControlFlow::WaitUntil(next_frame),
+                        ControlFlow::Poll => ControlFlow::WaitUntil(next_frame),
+                        ControlFlow::WaitUntil(instant) => {
+                            ControlFlow::WaitUntil(instant.max(next_frame))
+                        }
+                        control_flow => control_flow,
+                    };
+                    if *control_flow == ControlFlow::WaitUntil(next_frame) {
+                        winit_state.frame_target = Some(next_frame);
+                    }
+                }
             }
 
             _ => (),
@@ -698,6 +908,11 @@ pub fn winit_runner(mut app: App) {
             create_window(event_loop, create_window_parameters);
 
             create_window_system_state.apply(&mut app.world);
+
+            let tasks = app.world.non_send_resource_mut::<EventLoopTasks>().take();
+            for task in tasks {
+                task(event_loop, &mut app.world);
+            }
         }
     };
 
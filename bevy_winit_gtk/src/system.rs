@@ -2,16 +2,19 @@
 use bevy::a11y::AccessibilityRequested;
 use bevy::ecs::{
     entity::Entity,
-    event::EventWriter,
-    prelude::{Changed, Component, Resource},
+    event::{EventReader, EventWriter},
+    prelude::{Changed, Component, Or, Resource},
     removal_detection::RemovedComponents,
     system::{NonSendMut, Query},
 };
+use bevy::input::{mouse::MouseButtonInput, ButtonState};
 use bevy::utils::{
     tracing::{error, info, warn},
     HashMap,
 };
-use bevy::window::{RawHandleWrapper, Window, WindowClosed, WindowCreated};
+use bevy::window::{
+    CursorMoved, RawHandleWrapper, Window, WindowClosed, WindowCreated, WindowFocused,
+};
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 
 use winit::{
@@ -26,7 +29,7 @@ use crate::web_resize::{CanvasParentResizeEventChannel, WINIT_CANVAS_SELECTOR};
 use crate::FullWindowParams;
 use crate::{
     converters::{self, convert_window_level, convert_window_theme, convert_winit_theme},
-    get_best_videomode, get_fitting_videomode, WinitWindows,
+    get_best_videomode, get_fitting_videomode, DragRequest, WindowRequests, WinitWindows,
 };
 
 /// System responsible for creating new windows whenever a [`Window`] component is added
@@ -66,7 +69,8 @@ pub(crate) fn create_window<'a>(event_loop: &EventLoopWindowTarget<()>, params:
             })
             .insert(CachedWindow {
                 window: window.clone(),
-            });
+            })
+            .insert(WindowRequests::default());
 
         #[cfg(target_arch = "wasm32")]
         {
@@ -105,6 +109,46 @@ pub(crate) fn despawn_window(
     }
 }
 
+/// Clear attention requests of windows that just got focused.
+pub(crate) fn clear_attention_on_focus(
+    mut focused: EventReader<WindowFocused>,
+    mut requests: Query<&mut WindowRequests>,
+) {
+    for event in focused.iter().filter(|event| event.focused) {
+        if let Ok(mut requests) = requests.get_mut(event.window) {
+            if requests.attention().is_some() {
+                requests.clear_attention();
+            }
+        }
+    }
+}
+
+/// Start the title bar moves of windows once the cursor moves, or cancel them when
+/// the button is released, see [`WindowRequests::title_bar_pressed`].
+pub(crate) fn pending_title_bar_moves(
+    mut cursor_moved: EventReader<CursorMoved>,
+    mut buttons: EventReader<MouseButtonInput>,
+    mut requests: Query<&mut WindowRequests>,
+) {
+    for event in buttons.iter() {
+        if event.state != ButtonState::Released {
+            continue;
+        }
+        if let Ok(mut requests) = requests.get_mut(event.window) {
+            if requests.move_pending() {
+                requests.button_released(event.button);
+            }
+        }
+    }
+    for event in cursor_moved.iter() {
+        if let Ok(mut requests) = requests.get_mut(event.window) {
+            if requests.move_pending() {
+                requests.cursor_moved(event.position);
+            }
+        }
+    }
+}
+
 /// The cached state of the window so we can check which properties were changed from within the app.
 #[derive(Debug, Clone, Component)]
 pub struct CachedWindow {
@@ -119,10 +163,18 @@ pub struct CachedWindow {
 // - [`Window::canvas`] currently cannot be updated after startup, not entirely sure if it would work well with the
 //   event channel stuff.
 pub(crate) fn changed_window(
-    mut changed_windows: Query<(Entity, &mut Window, &mut CachedWindow), Changed<Window>>,
+    mut changed_windows: Query<
+        (
+            Entity,
+            &mut Window,
+            &mut CachedWindow,
+            Option<&mut WindowRequests>,
+        ),
+        Or<(Changed<Window>, Changed<WindowRequests>)>,
+    >,
     winit_windows: NonSendMut<WinitWindows>,
 ) {
-    for (entity, mut window, mut cache) in &mut changed_windows {
+    for (entity, mut window, mut cache, mut requests) in &mut changed_windows {
         if let Some(winit_window) = winit_windows.get_window(entity) {
             if window.title != cache.window.title {
                 winit_window.set_title(window.title.as_str());
@@ -252,6 +304,24 @@ pub(crate) fn changed_window(
                 winit_window.focus_window();
             }
 
+            // Bypass change detection, otherwise we would run this system on this window every frame.
+            let mut requests = requests.as_mut().map(|r| r.bypass_change_detection());
+            let drag_request = requests.as_mut().and_then(|r| r.take_drag_request());
+            if let Some(request) = drag_request {
+                let result = start_drag(winit_window, request);
+                if let Err(err) = result {
+                    warn!(
+                        "Could not start {request:?} for window {:?}: {:?}",
+                        window.title, err
+                    );
+                }
+            }
+
+            if let Some(attention) = requests.as_mut().and_then(|r| r.take_attention_request()) {
+                winit_window
+                    .request_user_attention(attention.map(converters::convert_user_attention));
+            }
+
             if window.window_level != cache.window.window_level {
                 winit_window.set_window_level(convert_window_level(window.window_level));
             }
@@ -291,3 +361,60 @@ pub(crate) fn changed_window(
         }
     }
 }
+
+/// Hand over an interactive move or resize to the window manager.
+///
+/// With `winit-gtk`, we go through GTK directly, so that the drag is tied to the
+/// button actually held down. Plain `winit` always drags with the primary button.
+#[cfg(all(target_os = "linux", feature = "winit-gtk"))]
+fn start_drag(
+    winit_window: &winit::window::Window,
+    request: DragRequest,
+) -> Result<(), winit::error::ExternalError> {
+    use gtk::prelude::{GtkWindowExt, WidgetExt};
+    use winit::platform::unix::WindowExtUnix;
+
+    let gtk_window = winit_window.gtk_window();
+    let pointer = gtk_window
+        .display()
+        .default_seat()
+        .and_then(|seat| seat.pointer());
+    let position = pointer.map(|pointer| {
+        let (_, x, y) = pointer.position();
+        (x, y)
+    });
+    let timestamp = gtk::current_event_time();
+    match (request, position) {
+        (DragRequest::Move { button }, Some((x, y))) => {
+            gtk_window.begin_move_drag(converters::convert_gtk_button(button), x, y, timestamp);
+        }
+        (DragRequest::Resize { edge, button }, Some((x, y))) => {
+            let edge = converters::convert_gtk_resize_edge(edge);
+            let button = converters::convert_gtk_button(button);
+            gtk_window.begin_resize_drag(edge, button, x, y, timestamp);
+        }
+        (DragRequest::ToggleMaximize, _) => {
+            winit_window.set_maximized(!winit_window.is_maximized());
+        }
+        (request, None) => {
+            warn!("Could not start {request:?}: no pointer device found");
+        }
+    }
+    Ok(())
+}
+#[cfg(not(all(target_os = "linux", feature = "winit-gtk")))]
+fn start_drag(
+    winit_window: &winit::window::Window,
+    request: DragRequest,
+) -> Result<(), winit::error::ExternalError> {
+    match request {
+        DragRequest::Move { .. } => winit_window.drag_window(),
+        DragRequest::Resize { edge, .. } => {
+            winit_window.drag_resize_window(converters::convert_resize_edge(edge))
+        }
+        DragRequest::ToggleMaximize => {
+            winit_window.set_maximized(!winit_window.is_maximized());
+            Ok(())
+        }
+    }
+}
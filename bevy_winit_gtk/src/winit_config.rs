@@ -1,5 +1,8 @@
-use bevy::ecs::system::Resource;
-use bevy::utils::Duration;
+use bevy::ecs::{entity::Entity, event::Event, prelude::Component, system::Resource};
+use bevy::utils::{Duration, HashSet};
+use bevy::window::Window;
+
+use crate::frame_pacing;
 
 /// A resource for configuring usage of the [`winit`] library.
 #[derive(Debug, Resource)]
@@ -31,6 +34,16 @@ pub struct WinitSettings {
     pub focused_mode: UpdateMode,
     /// Configures how the winit event loop updates while the window is *not* focused.
     pub unfocused_mode: UpdateMode,
+    /// The maximum number of app updates per second while a window is focused,
+    /// `None` to update as often as the [`UpdateMode`] allows.
+    ///
+    /// Between frames, the runner sleeps until the next frame is due, see
+    /// [`FRAME_PACING_JITTER`](crate::FRAME_PACING_JITTER) to measure how precise this is.
+    pub frame_rate_limit: Option<f64>,
+    /// The maximum number of app updates per second while no window is focused.
+    ///
+    /// When `None`, [`frame_rate_limit`](Self::frame_rate_limit) is used instead.
+    pub background_frame_rate_limit: Option<f64>,
 }
 impl WinitSettings {
     /// Configure winit with common settings for a game.
@@ -58,6 +71,27 @@ impl WinitSettings {
             false => &self.unfocused_mode,
         }
     }
+
+    /// Gets the minimum time between two frames, depending on whether a window is focused or not.
+    pub fn frame_duration(&self, focused: bool) -> Option<Duration> {
+        let limit = match focused {
+            true => self.frame_rate_limit,
+            false => self.background_frame_rate_limit.or(self.frame_rate_limit),
+        };
+        frame_pacing::frame_duration(limit)
+    }
+
+    /// Gets the [`UpdateMode`] of `window`, using its [`WindowUpdateMode`] if it has one.
+    pub fn window_update_mode<'a>(
+        &'a self,
+        window: &Window,
+        window_mode: Option<&'a WindowUpdateMode>,
+    ) -> &'a UpdateMode {
+        match window_mode {
+            Some(window_mode) => window_mode.update_mode(window.focused),
+            None => self.update_mode(window.focused),
+        }
+    }
 }
 impl Default for WinitSettings {
     fn default() -> Self {
@@ -65,6 +99,8 @@ impl Default for WinitSettings {
             return_from_run: false,
             focused_mode: UpdateMode::Continuous,
             unfocused_mode: UpdateMode::Continuous,
+            frame_rate_limit: None,
+            background_frame_rate_limit: None,
         }
     }
 }
@@ -111,3 +147,61 @@ pub enum UpdateMode {
         max_wait: Duration,
     },
 }
+
+/// Per-window override of the [`WinitSettings`] update modes.
+///
+/// Add this to a window entity so that it is updated according to its own focus state,
+/// independently of other windows. For example, a static inspector window could use
+/// [`UpdateMode::ReactiveLowPower`] to only be redrawn when interacted with,
+/// while the main viewport is [`UpdateMode::Continuous`].
+#[derive(Debug, Component)]
+pub struct WindowUpdateMode {
+    /// Configures how the winit event loop updates while this window is focused.
+    pub focused_mode: UpdateMode,
+    /// Configures how the winit event loop updates while this window is *not* focused.
+    pub unfocused_mode: UpdateMode,
+}
+impl WindowUpdateMode {
+    /// Update this window only when interacted with, or when a redraw is requested.
+    pub fn on_demand(max_wait: Duration) -> Self {
+        WindowUpdateMode {
+            focused_mode: UpdateMode::ReactiveLowPower { max_wait },
+            unfocused_mode: UpdateMode::ReactiveLowPower { max_wait },
+        }
+    }
+
+    /// Gets the configured [`UpdateMode`] depending on whether the window is focused or not
+    pub fn update_mode(&self, focused: bool) -> &UpdateMode {
+        match focused {
+            true => &self.focused_mode,
+            false => &self.unfocused_mode,
+        }
+    }
+}
+
+/// Request a redraw of a single window.
+///
+/// Unlike [`RequestRedraw`](bevy::window::RequestRedraw), this only wakes up `window`
+/// if it uses [`UpdateMode::ReactiveLowPower`].
+#[derive(Debug, Clone, Copy, Event)]
+pub struct RequestWindowRedraw {
+    /// The window to redraw.
+    pub window: Entity,
+}
+
+/// The windows that need to be redrawn during the current app update.
+///
+/// Set by the runner before each update. A window is in this set if its [`UpdateMode`]
+/// (or [`WindowUpdateMode`]) caused the update, or if a redraw was requested for it.
+///
+/// With the `render` feature, the renderer skips the other windows, which keep showing
+/// their last frame, unless their [`Window`] changed. Use this to also skip expensive
+/// per-window work on windows that didn't change.
+#[derive(Debug, Default, Resource)]
+pub struct WindowsToRedraw(pub HashSet<Entity>);
+impl WindowsToRedraw {
+    /// Whether `window` needs to be redrawn this update.
+    pub fn contains(&self, window: Entity) -> bool {
+        self.0.contains(&window)
+    }
+}
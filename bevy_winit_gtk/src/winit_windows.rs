@@ -1,12 +1,13 @@
 #![warn(missing_docs)]
 
-use bevy::ecs::entity::Entity;
+use bevy::ecs::{entity::Entity, world::World};
 
-use bevy::utils::{tracing::warn, HashMap};
+use bevy::utils::{tracing::warn, HashMap, HashSet};
 use bevy::window::{CursorGrabMode, Window, WindowMode, WindowPosition, WindowResolution};
 
 use winit::{
     dpi::{LogicalSize, PhysicalPosition},
+    event_loop::EventLoopWindowTarget,
     monitor::MonitorHandle,
 };
 
@@ -15,6 +16,26 @@ use crate::{
     AccessibilityWindowParams,
 };
 
+type EventLoopTask = Box<dyn FnOnce(&EventLoopWindowTarget<()>, &mut World)>;
+
+/// A `NonSend` resource of work that needs the `winit` event loop, such as creating
+/// windows not managed by bevy.
+///
+/// Tasks run right after new [`Window`]s got their `winit` window, so they can access
+/// windows spawned during the same frame through [`WinitWindows`].
+#[derive(Default)]
+pub struct EventLoopTasks(Vec<EventLoopTask>);
+impl EventLoopTasks {
+    /// Run `task` the next time the runner creates windows.
+    pub fn push(&mut self, task: impl FnOnce(&EventLoopWindowTarget<()>, &mut World) + 'static) {
+        self.0.push(Box::new(task));
+    }
+
+    pub(crate) fn take(&mut self) -> Vec<EventLoopTask> {
+        std::mem::take(&mut self.0)
+    }
+}
+
 /// A resource which maps window entities to [`winit`] library windows.
 #[derive(Debug, Default)]
 pub struct WinitWindows {
@@ -24,6 +45,11 @@ pub struct WinitWindows {
     pub entity_to_winit: HashMap<Entity, winit::window::WindowId>,
     /// Maps `winit` window identifiers to entities.
     pub winit_to_entity: HashMap<winit::window::WindowId, Entity>,
+    /// Maps identifiers of `winit` windows we do not own, such as webview windows,
+    /// to the window entity they are attached to.
+    pub attached_to_entity: HashMap<winit::window::WindowId, Entity>,
+    /// `winit` windows that currently have focus, including attached windows.
+    focused: HashSet<winit::window::WindowId>,
 
     // Some winit functions, such as `set_window_icon` can only be used from the main thread. If
     // they are used in another thread, the app will hang. This marker ensures `WinitWindows` is
@@ -197,11 +223,53 @@ impl WinitWindows {
         self.winit_to_entity.get(&winit_id).cloned()
     }
 
+    /// Attach a `winit` window not managed by bevy to the window entity `host`.
+    ///
+    /// Events of attached windows are not forwarded to bevy, except for close requests,
+    /// which are sent as a close request of `host`.
+    pub fn attach_window(&mut self, winit_id: winit::window::WindowId, host: Entity) {
+        self.attached_to_entity.insert(winit_id, host);
+    }
+
+    /// Stop tracking a window attached with [`Self::attach_window`].
+    pub fn detach_window(&mut self, winit_id: winit::window::WindowId) -> Option<Entity> {
+        self.focused.remove(&winit_id);
+        self.attached_to_entity.remove(&winit_id)
+    }
+
+    /// Get the window entity a non-bevy window is attached to.
+    pub fn get_attached_window_host(&self, winit_id: winit::window::WindowId) -> Option<Entity> {
+        self.attached_to_entity.get(&winit_id).copied()
+    }
+
+    /// Record a focus change of the `winit` window `winit_id`.
+    pub(crate) fn set_focused(&mut self, winit_id: winit::window::WindowId, focused: bool) {
+        if focused {
+            self.focused.insert(winit_id);
+        } else {
+            self.focused.remove(&winit_id);
+        }
+    }
+
+    /// Whether the window of `entity`, or a window attached to it, has focus.
+    pub fn is_entity_focused(&self, entity: Entity) -> bool {
+        let window_focused = self
+            .entity_to_winit
+            .get(&entity)
+            .map_or(false, |winit_id| self.focused.contains(winit_id));
+        window_focused
+            || self
+                .attached_to_entity
+                .iter()
+                .any(|(winit_id, host)| *host == entity && self.focused.contains(winit_id))
+    }
+
     /// Remove a window from winit.
     ///
     /// This should mostly just be called when the window is closing.
     pub fn remove_window(&mut self, entity: Entity) -> Option<winit::window::Window> {
         let winit_id = self.entity_to_winit.remove(&entity)?;
+        self.focused.remove(&winit_id);
         // Don't remove from winit_to_window_id, to track that we used to know about this winit window
         self.windows.remove(&winit_id)
     }
@@ -0,0 +1,327 @@
+//! The `bevy://` custom protocol, serving bevy assets to webviews.
+//!
+//! `<img src="bevy://icons/sword.png">` loads `icons/sword.png` from the bevy asset
+//! folder, through the `AssetServer`'s asset IO. `Image` assets that only live in
+//! memory, such as render targets, can be registered in [`WebImages`] and are served
+//! as PNG at `bevy://memory/<name>`.
+//!
+//! On windows, custom protocols are served as `https://bevy.<path>` instead, such as
+//! `https://bevy.icons/sword.png`.
+use std::borrow::Cow;
+use std::io::Cursor;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use futures_lite::future::block_on;
+use percent_encoding::percent_decode_str;
+use wry::http::{header, Request, Response, StatusCode, Uri};
+
+/// The name of the custom protocol.
+pub const SCHEME: &str = "bevy";
+
+/// The `bevy://` host of images registered in [`WebImages`].
+const MEMORY_HOST: &str = "memory";
+
+type EncodedImages = Arc<RwLock<HashMap<String, Arc<[u8]>>>>;
+
+/// In-memory `Image` assets served to webviews at `bevy://memory/<name>`, as PNG.
+///
+/// Images are encoded again whenever they are modified.
+#[derive(Resource, Default)]
+pub struct WebImages {
+    handles: HashMap<String, Handle<Image>>,
+    to_encode: HashSet<String>,
+    encoded: EncodedImages,
+}
+impl WebImages {
+    /// Serve `image` at `bevy://memory/<name>`.
+    pub fn insert(&mut self, name: impl Into<String>, image: Handle<Image>) {
+        let name = name.into();
+        self.to_encode.insert(name.clone());
+        self.handles.insert(name, image);
+    }
+    /// Stop serving the image at `bevy://memory/<name>`.
+    pub fn remove(&mut self, name: &str) -> Option<Handle<Image>> {
+        self.to_encode.remove(name);
+        self.encoded.write().unwrap().remove(name);
+        self.handles.remove(name)
+    }
+}
+
+pub struct AssetProtocolPlugin;
+impl Plugin for AssetProtocolPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WebImages>()
+            .add_systems(Last, encode_web_images);
+    }
+}
+
+fn encode_web_images(
+    mut web_images: ResMut<WebImages>,
+    mut events: EventReader<AssetEvent<Image>>,
+    images: Res<Assets<Image>>,
+) {
+    let web_images = &mut *web_images;
+    for event in events.iter() {
+        let (AssetEvent::Created { handle } | AssetEvent::Modified { handle }) = event else {
+            continue;
+        };
+        let modified = web_images.handles.iter().filter(|(_, h)| *h == handle);
+        web_images
+            .to_encode
+            .extend(modified.map(|(name, _)| name.clone()));
+    }
+    web_images.to_encode.retain(|name| {
+        let Some(image) = images.get(&web_images.handles[name]) else {
+            // Not loaded yet, we will get a `Created` event when it is.
+            return true;
+        };
+        match encode_png(image) {
+            Ok(png) => {
+                let mut encoded = web_images.encoded.write().unwrap();
+                encoded.insert(name.clone(), png.into());
+            }
+            Err(err) => warn!("Could not serve image {name:?} to webviews: {err}"),
+        }
+        false
+    });
+}
+
+fn encode_png(image: &Image) -> Result<Vec<u8>, String> {
+    let image = image
+        .clone()
+        .try_into_dynamic()
+        .map_err(|err| err.to_string())?;
+    let mut png = Cursor::new(Vec::new());
+    image
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .map_err(|err| err.to_string())?;
+    Ok(png.into_inner())
+}
+
+/// The `bevy://` protocol handler, to pass to `WebViewBuilder::with_custom_protocol`.
+pub fn handler(
+    world: &World,
+) -> impl Fn(&Request<Vec<u8>>) -> wry::Result<Response<Cow<'static, [u8]>>> + 'static {
+    let asset_server = world.resource::<AssetServer>().clone();
+    let encoded = world.resource::<WebImages>().encoded.clone();
+    move |request| {
+        let Some(path) = request_path(request.uri()) else {
+            return Ok(not_found());
+        };
+        if let Ok(name) = path.strip_prefix(MEMORY_HOST) {
+            let png = name
+                .to_str()
+                .and_then(|name| encoded.read().unwrap().get(name).cloned());
+            return Ok(match png {
+                Some(png) => serve(request, "image/png", &png),
+                None => not_found(),
+            });
+        }
+        Ok(match block_on(asset_server.asset_io().load_path(&path)) {
+            Ok(bytes) => serve(request, mime_type(&path), &bytes),
+            Err(err) => {
+                debug!("bevy:// request for {path:?} failed: {err}");
+                not_found()
+            }
+        })
+    }
+}
+
+/// The relative file path requested by `uri`, host included.
+///
+/// Returns `None` if the path tries to escape its root, for example with `..`.
+pub fn request_path(uri: &Uri) -> Option<PathBuf> {
+    let host = uri.host().unwrap_or_default();
+    // On windows, `<scheme>://<host>` is requested as `https://<scheme>.<host>`.
+    let host = match uri.scheme_str() {
+        Some("https") => host.split_once('.').map_or(host, |(_, host)| host),
+        _ => host,
+    };
+    let path = percent_decode_str(uri.path()).decode_utf8().ok()?;
+    let path = Path::new(host).join(path.trim_start_matches('/'));
+    let normal = path.components().all(|c| matches!(c, Component::Normal(_)));
+    normal.then_some(path)
+}
+
+/// The MIME type of a file, based on its extension.
+pub fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    match extension.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html",
+        "js" | "mjs" => "text/javascript",
+        "css" => "text/css",
+        "json" | "map" => "application/json",
+        "txt" | "ron" | "ftl" => "text/plain",
+        "wasm" => "application/wasm",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "mp3" => "audio/mpeg",
+        "ogg" | "oga" => "audio/ogg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Respond to `request` with `bytes`, honoring the `Range` header if any.
+pub fn serve(
+    request: &Request<Vec<u8>>,
+    mime_type: &str,
+    bytes: &[u8],
+) -> Response<Cow<'static, [u8]>> {
+    let builder = Response::builder()
+        .header(header::CONTENT_TYPE, mime_type)
+        .header(header::ACCEPT_RANGES, "bytes");
+
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|r| r.to_str().ok());
+    let range = range.map_or(Range::Full, |range| parse_range(range, bytes.len()));
+    let response = match range {
+        Range::Full => builder.body(Cow::Owned(bytes.to_vec())),
+        Range::Partial(start, end) => builder
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(
+                header::CONTENT_RANGE,
+                format!("bytes {start}-{end}/{}", bytes.len()),
+            )
+            .body(Cow::Owned(bytes[start..=end].to_vec())),
+        Range::Unsatisfiable => builder
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{}", bytes.len()))
+            .body(Cow::Borrowed(&[][..])),
+    };
+    response.unwrap()
+}
+
+pub fn not_found() -> Response<Cow<'static, [u8]>> {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Cow::Borrowed(&[][..]))
+        .unwrap()
+}
+
+/// What to serve for a `Range` header.
+#[derive(Debug, PartialEq, Eq)]
+enum Range {
+    /// The whole body.
+    Full,
+    /// The inclusive byte range.
+    Partial(usize, usize),
+    Unsatisfiable,
+}
+
+/// Parse a single range `Range` header of a body of `len` bytes.
+///
+/// Multiple ranges and invalid headers are ignored, and the whole body is served.
+fn parse_range(range: &str, len: usize) -> Range {
+    let Some((start, end)) = range
+        .strip_prefix("bytes=")
+        .filter(|range| !range.contains(','))
+        .and_then(|range| range.split_once('-'))
+    else {
+        return Range::Full;
+    };
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => match suffix.parse::<usize>() {
+            Ok(0) => return Range::Unsatisfiable,
+            Ok(suffix) => (len.saturating_sub(suffix), None),
+            Err(_) => return Range::Full,
+        },
+        (start, "") => match start.parse() {
+            Ok(start) => (start, None),
+            Err(_) => return Range::Full,
+        },
+        (start, end) => match (start.parse(), end.parse::<usize>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, Some(end)),
+            _ => return Range::Full,
+        },
+    };
+    if start >= len {
+        return Range::Unsatisfiable;
+    }
+    Range::Partial(start, end.map_or(len - 1, |end| end.min(len - 1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{parse_range, request_path, Range};
+
+    fn path(uri: &str) -> Option<std::path::PathBuf> {
+        request_path(&uri.parse().unwrap())
+    }
+
+    #[test]
+    fn request_path_keeps_the_host() {
+        assert_eq!(
+            path("bevy://icons/sword.png").as_deref(),
+            Some(Path::new("icons/sword.png"))
+        );
+        assert_eq!(
+            path("bevy://icons/a%20b.png").as_deref(),
+            Some(Path::new("icons/a b.png"))
+        );
+    }
+
+    #[test]
+    fn request_path_of_windows_urls() {
+        assert_eq!(
+            path("https://bevy.icons/sword.png").as_deref(),
+            Some(Path::new("icons/sword.png"))
+        );
+    }
+
+    #[test]
+    fn request_path_rejects_escapes() {
+        assert_eq!(path("bevy://icons/../../etc/passwd"), None);
+        assert_eq!(path("bevy://icons/..%2f..%2fetc/passwd"), None);
+    }
+
+    #[test]
+    fn parse_single_ranges() {
+        assert_eq!(parse_range("bytes=2-5", 10), Range::Partial(2, 5));
+        // Open-ended.
+        assert_eq!(parse_range("bytes=4-", 10), Range::Partial(4, 9));
+        // Suffix, the last bytes.
+        assert_eq!(parse_range("bytes=-3", 10), Range::Partial(7, 9));
+        assert_eq!(parse_range("bytes=-30", 10), Range::Partial(0, 9));
+        // Clamped to the body.
+        assert_eq!(parse_range("bytes=8-20", 10), Range::Partial(8, 9));
+    }
+
+    #[test]
+    fn parse_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=10-", 10), Range::Unsatisfiable);
+        assert_eq!(parse_range("bytes=12-20", 10), Range::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 10), Range::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-5", 0), Range::Unsatisfiable);
+    }
+
+    #[test]
+    fn ignore_multiple_and_invalid_ranges() {
+        assert_eq!(parse_range("bytes=0-1, 4-5", 10), Range::Full);
+        assert_eq!(parse_range("bytes=5-2", 10), Range::Full);
+        assert_eq!(parse_range("bytes=a-b", 10), Range::Full);
+        assert_eq!(parse_range("items=0-1", 10), Range::Full);
+        assert_eq!(parse_range("bytes=3", 10), Range::Full);
+    }
+}
@@ -0,0 +1,462 @@
+//! Binary buffers shared with webviews, through the `buffer://` custom protocol.
+//!
+//! Bevy publishes byte buffers for a webview under an id with [`BinaryBuffers::publish`],
+//! and its pages fetch them at `buffer://data/<id>`. Pages upload buffers to the same
+//! url with `POST`, bevy reads them with [`BinaryBuffers::get`] once it got
+//! [`BufferUploaded`]. Ids are scoped to each webview: a page only sees the buffers of
+//! its own webview, and can't overwrite the buffers published by bevy. Only pages from
+//! the UI origin, [`ui_bundle::ORIGIN`], can read the responses, or upload and release
+//! buffers.
+//!
+//! A buffer is freed once both sides released it: bevy with [`BinaryBuffers::release`],
+//! pages with a `DELETE` request. Bevy can also take back a buffer it published with
+//! [`BinaryBuffers::revoke`], even if pages didn't release it. All buffers together
+//! can't exceed [`BinaryBuffers::capacity`] bytes, publishing or uploading more fails.
+//! The buffers of a webview are freed when it is closed.
+//!
+//! From JS, with the `bevy.buffers` object defined by [`BUFFERS_SCRIPT`]:
+//!
+//! ```js
+//! const heightmap = await bevy.buffers.fetch("heightmap"); // ArrayBuffer
+//! await bevy.buffers.release("heightmap");
+//! await bevy.buffers.upload("thumbnail", canvasBytes);
+//! ```
+//!
+//! Uploading needs a webview engine passing request bodies to custom protocols,
+//! WebKitGTK 2.40 or later on linux.
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use wry::http::{header, Method, Request, Response, StatusCode};
+
+use crate::asset_protocol::{not_found, request_path, serve};
+use crate::ui_bundle;
+use crate::webview::WryWebviews;
+
+/// The name of the custom protocol.
+pub const SCHEME: &str = "buffer";
+
+const HOST: &str = "data";
+
+/// Default [`BinaryBuffers::capacity`], 64 MiB.
+const DEFAULT_CAPACITY: usize = 64 * 1024 * 1024;
+
+/// Initialization script defining `bevy.buffers`, it must run after
+/// [`crate::bridge::BRIDGE_SCRIPT`].
+pub const BUFFERS_SCRIPT: &str = r#"(() => {
+    const url = (id) => `buffer://data/${encodeURIComponent(id)}`;
+    async function check(response) {
+        if (!response.ok) {
+            throw new Error(`buffer request failed: ${response.status} ${await response.text()}`);
+        }
+        return response;
+    }
+    bevy.buffers = {
+        url,
+        fetch: async (id) => (await check(await fetch(url(id)))).arrayBuffer(),
+        upload: async (id, bytes) => { await check(await fetch(url(id), { method: "POST", body: bytes })); },
+        release: async (id) => { await check(await fetch(url(id), { method: "DELETE" })); },
+    };
+})();"#;
+
+/// A page of `webview` uploaded the buffer `id`, read it with [`BinaryBuffers::get`].
+#[derive(Event, Debug, Clone)]
+pub struct BufferUploaded {
+    pub webview: Entity,
+    pub id: String,
+}
+
+/// Why a buffer couldn't be stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BufferError {
+    /// Storing it would use more than [`BinaryBuffers::capacity`] bytes.
+    OverCapacity { size: usize, available: usize },
+    /// A page tried to replace a buffer published by bevy.
+    Published,
+}
+impl fmt::Display for BufferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BufferError::OverCapacity { size, available } => write!(
+                f,
+                "buffer of {size} bytes doesn't fit in the {available} bytes available"
+            ),
+            BufferError::Published => write!(f, "buffer published by bevy, pages can't replace it"),
+        }
+    }
+}
+impl std::error::Error for BufferError {}
+
+struct Buffer {
+    bytes: Arc<[u8]>,
+    from_page: bool,
+    released_by_bevy: bool,
+    released_by_page: bool,
+}
+
+/// A buffer id, in the scope of a webview.
+type BufferKey = (Entity, String);
+
+#[derive(Default)]
+struct Store {
+    buffers: HashMap<BufferKey, Buffer>,
+    /// Total size of `buffers`.
+    size: usize,
+    capacity: usize,
+    uploaded: Vec<BufferKey>,
+}
+impl Store {
+    fn insert(
+        &mut self,
+        key: BufferKey,
+        bytes: Arc<[u8]>,
+        from_page: bool,
+    ) -> Result<(), BufferError> {
+        let replaced = self.buffers.get(&key);
+        if from_page && replaced.map_or(false, |buffer| !buffer.from_page) {
+            return Err(BufferError::Published);
+        }
+        let replaced = replaced.map_or(0, |buffer| buffer.bytes.len());
+        let available = self.capacity - (self.size - replaced);
+        if bytes.len() > available {
+            return Err(BufferError::OverCapacity { size: bytes.len(), available });
+        }
+        self.size = self.size - replaced + bytes.len();
+        let buffer = Buffer {
+            bytes,
+            from_page,
+            // The side that stores a buffer is done with it.
+            released_by_bevy: !from_page,
+            released_by_page: from_page,
+        };
+        self.buffers.insert(key, buffer);
+        Ok(())
+    }
+    fn release(&mut self, key: &BufferKey, by_page: bool) -> bool {
+        let Some(buffer) = self.buffers.get_mut(key) else {
+            return false;
+        };
+        if by_page {
+            buffer.released_by_page = true;
+        } else {
+            buffer.released_by_bevy = true;
+        }
+        if buffer.released_by_bevy && buffer.released_by_page {
+            self.remove(key);
+        }
+        true
+    }
+    fn remove(&mut self, key: &BufferKey) -> bool {
+        let Some(buffer) = self.buffers.remove(key) else {
+            return false;
+        };
+        self.size -= buffer.bytes.len();
+        true
+    }
+    /// Free the buffers of the webviews not passing `keep`.
+    fn retain_webviews(&mut self, keep: impl Fn(Entity) -> bool) {
+        let mut freed = 0;
+        self.buffers.retain(|(webview, _), buffer| {
+            let kept = keep(*webview);
+            if !kept {
+                freed += buffer.bytes.len();
+            }
+            kept
+        });
+        self.size -= freed;
+        self.uploaded.retain(|(webview, _)| keep(*webview));
+    }
+}
+
+/// The binary buffers shared with webviews.
+///
+/// This is cheap to clone, clones share the same buffers.
+#[derive(Resource, Clone)]
+pub struct BinaryBuffers(Arc<Mutex<Store>>);
+impl Default for BinaryBuffers {
+    fn default() -> Self {
+        let store = Store { capacity: DEFAULT_CAPACITY, ..default() };
+        BinaryBuffers(Arc::new(Mutex::new(store)))
+    }
+}
+impl BinaryBuffers {
+    /// Serve `bytes` at `buffer://data/<id>` to the pages of `webview`, replacing the
+    /// buffer with this id.
+    ///
+    /// It is kept until a page releases it, or it is revoked with [`BinaryBuffers::revoke`].
+    pub fn publish(
+        &self,
+        webview: Entity,
+        id: impl Into<String>,
+        bytes: impl Into<Arc<[u8]>>,
+    ) -> Result<(), BufferError> {
+        let key = (webview, id.into());
+        self.0.lock().unwrap().insert(key, bytes.into(), false)
+    }
+    /// The buffer `id` of `webview`, published by bevy or uploaded by a page.
+    pub fn get(&self, webview: Entity, id: &str) -> Option<Arc<[u8]>> {
+        let store = self.0.lock().unwrap();
+        let buffer = store.buffers.get(&(webview, id.to_string()));
+        buffer.map(|buffer| buffer.bytes.clone())
+    }
+    /// Bevy is done with the uploaded buffer `id` of `webview`, free it once pages
+    /// released it as well.
+    pub fn release(&self, webview: Entity, id: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .release(&(webview, id.to_string()), false);
+    }
+    /// Free the buffer `id` of `webview` now, whether pages released it or not.
+    ///
+    /// Returns whether there was such a buffer.
+    pub fn revoke(&self, webview: Entity, id: &str) -> bool {
+        self.0.lock().unwrap().remove(&(webview, id.to_string()))
+    }
+    /// Maximum total size of the buffers, in bytes.
+    pub fn capacity(&self) -> usize {
+        self.0.lock().unwrap().capacity
+    }
+    /// Set the maximum total size of the buffers. Existing buffers are kept even if
+    /// they don't fit anymore.
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut store = self.0.lock().unwrap();
+        store.capacity = capacity.max(store.size);
+    }
+    /// Total size of the buffers, in bytes.
+    pub fn size(&self) -> usize {
+        self.0.lock().unwrap().size
+    }
+}
+
+pub struct BinaryPlugin;
+impl Plugin for BinaryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BinaryBuffers>()
+            .add_event::<BufferUploaded>()
+            .add_systems(PreUpdate, send_upload_events)
+            .add_systems(Last, free_closed_webviews);
+    }
+}
+
+fn send_upload_events(buffers: Res<BinaryBuffers>, mut events: EventWriter<BufferUploaded>) {
+    let uploaded = std::mem::take(&mut buffers.0.lock().unwrap().uploaded);
+    let uploaded = uploaded.into_iter();
+    events.send_batch(uploaded.map(|(webview, id)| BufferUploaded { webview, id }));
+}
+
+fn free_closed_webviews(buffers: Res<BinaryBuffers>, webviews: NonSend<WryWebviews>) {
+    let mut store = buffers.0.lock().unwrap();
+    store.retain_webviews(|webview| webviews.get(webview).is_some());
+}
+
+/// The `buffer://` protocol handler of `webview`, to pass to
+/// `WebViewBuilder::with_custom_protocol`.
+pub fn handler(
+    world: &World,
+    webview: Entity,
+) -> impl Fn(&Request<Vec<u8>>) -> wry::Result<Response<Cow<'static, [u8]>>> + 'static {
+    let buffers = world.resource::<BinaryBuffers>().clone();
+    move |request| {
+        let path = request_path(request.uri());
+        let id = path
+            .as_deref()
+            .and_then(|path| path.strip_prefix(HOST).ok())
+            .and_then(|id| id.to_str())
+            .filter(|id| !id.is_empty());
+        let Some(id) = id else {
+            return Ok(not_found());
+        };
+        let key = (webview, id.to_string());
+        // Any page can send requests, only the UI can change buffers.
+        let from_ui = request
+            .headers()
+            .get(header::ORIGIN)
+            .map_or(false, |origin| origin == ui_bundle::ORIGIN);
+        let response = match *request.method() {
+            Method::GET | Method::HEAD => match buffers.get(webview, id) {
+                Some(bytes) => {
+                    let mut response = serve(request, "application/octet-stream", &bytes);
+                    let origin = header::HeaderValue::from_static(ui_bundle::ORIGIN);
+                    let headers = response.headers_mut();
+                    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+                    response
+                }
+                None => not_found(),
+            },
+            Method::POST | Method::PUT | Method::DELETE if !from_ui => {
+                status(StatusCode::FORBIDDEN, String::new())
+            }
+            Method::POST | Method::PUT => {
+                let mut store = buffers.0.lock().unwrap();
+                let bytes = Arc::from(request.body().as_slice());
+                match store.insert(key.clone(), bytes, true) {
+                    Ok(()) => {
+                        store.uploaded.push(key);
+                        status(StatusCode::CREATED, String::new())
+                    }
+                    Err(err @ BufferError::Published) => {
+                        status(StatusCode::CONFLICT, err.to_string())
+                    }
+                    Err(err) => status(StatusCode::PAYLOAD_TOO_LARGE, err.to_string()),
+                }
+            }
+            Method::DELETE => match buffers.0.lock().unwrap().release(&key, true) {
+                true => status(StatusCode::NO_CONTENT, String::new()),
+                false => not_found(),
+            },
+            // CORS preflight of uploads and releases.
+            Method::OPTIONS => status(StatusCode::NO_CONTENT, String::new()),
+            _ => status(StatusCode::METHOD_NOT_ALLOWED, String::new()),
+        };
+        Ok(response)
+    }
+}
+
+fn status(status: StatusCode, body: String) -> Response<Cow<'static, [u8]>> {
+    Response::builder()
+        .status(status)
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, ui_bundle::ORIGIN)
+        .header(
+            header::ACCESS_CONTROL_ALLOW_METHODS,
+            "GET, HEAD, POST, PUT, DELETE",
+        )
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(Cow::Owned(body.into_bytes()))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use wry::http::{header, Method, Request, StatusCode};
+
+    use super::{handler, BinaryBuffers, BufferError, BufferKey, Store};
+    use crate::ui_bundle;
+
+    fn store(capacity: usize) -> Store {
+        Store { capacity, ..default() }
+    }
+
+    fn key(id: &str) -> BufferKey {
+        (Entity::from_raw(0), id.to_string())
+    }
+
+    fn bytes(len: usize) -> std::sync::Arc<[u8]> {
+        vec![0; len].into()
+    }
+
+    #[test]
+    fn insert_within_capacity() {
+        let mut store = store(10);
+        store.insert(key("a"), bytes(6), false).unwrap();
+        assert_eq!(store.size, 6);
+        let err = store.insert(key("b"), bytes(5), false);
+        assert_eq!(
+            err,
+            Err(BufferError::OverCapacity { size: 5, available: 4 })
+        );
+        assert_eq!(store.size, 6);
+
+        // Replacing a buffer frees its bytes first.
+        store.insert(key("a"), bytes(10), false).unwrap();
+        assert_eq!(store.size, 10);
+        store.insert(key("a"), bytes(2), false).unwrap();
+        assert_eq!(store.size, 2);
+    }
+
+    #[test]
+    fn freed_once_released_by_both_sides() {
+        let mut store = store(10);
+        store.insert(key("uploaded"), bytes(4), true).unwrap();
+        assert!(store.release(&key("uploaded"), true));
+        assert_eq!(store.size, 4);
+        assert!(store.release(&key("uploaded"), false));
+        assert_eq!(store.size, 0);
+        assert!(!store.release(&key("uploaded"), false));
+
+        store.insert(key("published"), bytes(4), false).unwrap();
+        assert!(store.release(&key("published"), true));
+        assert_eq!(store.size, 0);
+        assert!(store.buffers.is_empty());
+    }
+
+    #[test]
+    fn revoked_without_page_release() {
+        let mut store = store(10);
+        store.insert(key("published"), bytes(4), false).unwrap();
+        assert!(store.remove(&key("published")));
+        assert_eq!(store.size, 0);
+        assert!(!store.remove(&key("published")));
+    }
+
+    #[test]
+    fn pages_cant_replace_published_buffers() {
+        let mut store = store(10);
+        store.insert(key("published"), bytes(4), false).unwrap();
+        let err = store.insert(key("published"), bytes(2), true);
+        assert_eq!(err, Err(BufferError::Published));
+        assert_eq!(store.size, 4);
+
+        store.insert(key("uploaded"), bytes(2), true).unwrap();
+        store.insert(key("uploaded"), bytes(3), true).unwrap();
+        assert_eq!(store.size, 7);
+    }
+
+    #[test]
+    fn ids_are_scoped_per_webview() {
+        let mut store = store(10);
+        let other = (Entity::from_raw(1), "a".to_string());
+        store.insert(key("a"), bytes(4), false).unwrap();
+        store.insert(other.clone(), bytes(2), true).unwrap();
+        assert_eq!(store.size, 6);
+        assert_eq!(store.buffers[&key("a")].bytes.len(), 4);
+        assert!(store.release(&other, false));
+        assert_eq!(store.size, 4);
+    }
+
+    #[test]
+    fn closed_webviews_are_freed() {
+        let mut store = store(10);
+        let other = (Entity::from_raw(1), "a".to_string());
+        store.insert(key("a"), bytes(4), false).unwrap();
+        store.insert(key("b"), bytes(1), true).unwrap();
+        store.insert(other.clone(), bytes(2), true).unwrap();
+        store.uploaded = vec![key("b"), other.clone()];
+
+        store.retain_webviews(|webview| webview == other.0);
+        assert_eq!(store.size, 2);
+        assert_eq!(store.buffers.len(), 1);
+        assert_eq!(store.uploaded, [other]);
+    }
+
+    #[test]
+    fn uploads_need_the_ui_origin() {
+        let mut world = World::new();
+        world.init_resource::<BinaryBuffers>();
+        let webview = Entity::from_raw(0);
+        let handler = handler(&world, webview);
+        let upload = |origin: Option<&str>| {
+            let mut request = Request::builder()
+                .method(Method::POST)
+                .uri("buffer://data/a");
+            if let Some(origin) = origin {
+                request = request.header(header::ORIGIN, origin);
+            }
+            handler(&request.body(vec![1, 2]).unwrap())
+                .unwrap()
+                .status()
+        };
+
+        assert_eq!(upload(Some("https://example.com")), StatusCode::FORBIDDEN);
+        assert_eq!(upload(None), StatusCode::FORBIDDEN);
+        let buffers = world.resource::<BinaryBuffers>();
+        assert_eq!(buffers.get(webview, "a"), None);
+
+        assert_eq!(upload(Some(ui_bundle::ORIGIN)), StatusCode::CREATED);
+        assert_eq!(buffers.get(webview, "a").as_deref(), Some(&[1, 2][..]));
+    }
+}
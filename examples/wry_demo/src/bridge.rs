@@ -1,29 +1,157 @@
 use std::sync::mpsc::{channel, Receiver, Sender};
 
 use bevy::prelude::*;
+use serde::Deserialize;
+use ts_rs::TS;
 use wry::application::window::Window as TaoWindow;
 
-use crate::links::NewPage;
-use crate::WryWebview;
+use crate::dom_watch::{self, DomMatch};
+use crate::forward_input::{self, DomInput};
+use crate::inspector::{self, InspectorOp};
+use crate::ipc_policy::{self, IpcGuard, IpcViolationKind};
+use crate::navigation::{self, Navigation};
+use crate::offscreen;
+use crate::outbox::Outbox;
+use crate::overlay::{InteractiveRegions, Region};
+use crate::web_commands::{self, WebCommand};
+use crate::web_errors::WebViewError;
+use crate::web_log::{self, ConsoleLevel};
+use crate::web_process::{self, WebProcessEventKind};
+use crate::web_sync;
+use crate::webview::WryWebviews;
+
+/// Initialization script of all webviews, defining the `window.bevy` object.
+///
+/// `bevy.send(request)` posts a [`Request`]. `bevy.call(type, args)` posts a [`Request`]
+/// of this `type` with an `id`, and returns a promise of the answer sent with
+/// [`Outbox::reply`]. `bevy.on(channel, callback)` listens to the data sent with
+/// [`Outbox::push`], and returns a function removing the listener.
+pub const BRIDGE_SCRIPT: &str = r#"(() => {
+    let next_id = 0;
+    const pending = new Map();
+    const listeners = new Map();
+    window.bevy = {
+        send(request) {
+            window.ipc.postMessage(JSON.stringify(request));
+        },
+        call(type, args = {}) {
+            const id = next_id++;
+            return new Promise((resolve, reject) => {
+                pending.set(id, { resolve, reject });
+                window.ipc.postMessage(JSON.stringify({ ...args, type, id }));
+            });
+        },
+        on(channel, callback) {
+            if (!listeners.has(channel)) {
+                listeners.set(channel, new Set());
+            }
+            listeners.get(channel).add(callback);
+            return () => listeners.get(channel).delete(callback);
+        },
+        __receive(message) {
+            if (message.type === "Reply") {
+                const call = pending.get(message.id);
+                pending.delete(message.id);
+                if ("error" in message) {
+                    call?.reject(new Error(message.error));
+                } else {
+                    call?.resolve(message.value);
+                }
+            } else if (message.type === "Push") {
+                for (const callback of listeners.get(message.channel) ?? []) {
+                    callback(message.data);
+                }
+            }
+        },
+    };
+})();"#;
 
 /// Eventy sent to bevy from wry.
+///
+/// They are posted as JSON objects through the IPC, with the variant name as `type`,
+/// except for [`Request::Navigation`] and [`Request::WebProcess`], which come from
+/// the webview engine itself,
+/// and [`Request::Violation`], sent by the IPC handler for rejected messages.
+///
+/// Their TypeScript definition is generated in `ui/bridge.d.ts`, see `typescript.rs`.
+#[derive(Deserialize, Debug, TS)]
+#[serde(tag = "type")]
 pub enum Request {
-    SpawnNewLinks(Vec<String>),
+    InteractiveRegions {
+        regions: Vec<Region>,
+    },
+    Input {
+        input: DomInput,
+    },
+    /// The page of an offscreen webview changed.
+    Dirty,
+    /// A call to the ECS inspector, answered with [`Outbox::reply`].
+    Inspector {
+        #[ts(type = "number")]
+        id: u64,
+        #[serde(flatten)]
+        op: InspectorOp,
+    },
+    /// A command from `bevy.commands`, answered with [`Outbox::reply`] once applied.
+    Command {
+        #[ts(type = "number")]
+        id: u64,
+        #[serde(flatten)]
+        command: WebCommand,
+    },
+    /// A `bevy.subscribe` to a synced store, answered with its current value.
+    Subscribe {
+        #[ts(type = "number")]
+        id: u64,
+        store: String,
+    },
+    /// An uncaught error of the page, see [`crate::web_errors::ERRORS_SCRIPT`].
+    Error {
+        message: String,
+        source: Option<String>,
+        line: Option<u32>,
+    },
+    /// Changes to the matches of a [`crate::dom_watch::DomWatch`].
+    DomMatches {
+        watch: String,
+        added: Vec<DomMatch>,
+        changed: Vec<DomMatch>,
+        #[ts(type = "Array<number>")]
+        removed: Vec<u64>,
+    },
+    /// A `console` message of the page, see [`crate::web_log::CONSOLE_SCRIPT`].
+    Console {
+        level: ConsoleLevel,
+        message: String,
+    },
+    #[serde(skip_deserializing)]
+    #[ts(skip)]
+    Navigation(Navigation),
+    #[serde(skip_deserializing)]
+    #[ts(skip)]
+    WebProcess(WebProcessEventKind),
+    #[serde(skip_deserializing)]
+    #[ts(skip)]
+    Violation(IpcViolationKind),
 }
 
 /// Events sent from bevy to wry.
-#[derive(Event)]
+#[derive(Event, Debug, Clone, TS)]
+#[ts(rename = "BevyEvent")]
 pub enum Event {
     NavigateToPage(String),
 }
 
-#[derive(Deref, Debug)]
-pub struct WrySender(Sender<Request>);
-pub struct BevyReceiver(Receiver<Request>);
+/// A [`Request`] with the webview entity that sent it, and the origin of the page that
+/// posted it, `None` for requests that don't come from a page.
+#[derive(Deref, Debug, Clone)]
+pub struct WrySender(Sender<(Entity, Option<String>, Request)>);
+pub struct BevyReceiver(Receiver<(Entity, Option<String>, Request)>);
 
 impl Drop for BevyReceiver {
     fn drop(&mut self) {
-        error!("Dropping the bevy receiver, If this isn't the last log message, this is very suss");
+        // Expected when the app exits, otherwise the webviews can't reach bevy anymore.
+        debug!("Dropping the bridge receiver, requests from webviews are ignored from now on");
     }
 }
 
@@ -33,39 +161,77 @@ impl Event {
             Event::NavigateToPage(page) => format!("window.location.assign({page:?})"),
         }
     }
-    fn to_wry(&self, webview: &WryWebview) {
-        webview.0.evaluate_script(&self.command()).unwrap();
-    }
 }
 
-/// To use in `GetWindow` impl for `WryWebview`.
-pub fn wry_bridge(bridge: &WrySender, _window: &TaoWindow, request: String) {
-    let prefix = "NavigatedTo:";
-    let prefix_len = prefix.len();
-    if request.starts_with(prefix) {
-        info!("recognized request NavigatedTo");
-        let links = &request[prefix_len..];
-        let links: Vec<_> = links.split(',').map(str::to_string).collect();
-        bridge.send(Request::SpawnNewLinks(links)).unwrap();
-    } else {
-        error!("unrecognized request: {request}");
-    }
+/// The IPC handler of the webview of entity `webview`.
+///
+/// Messages over the limits of `guard` or that aren't a [`Request`] are reported
+/// as [`Request::Violation`]. Requests are sent with the origin of the page when they
+/// arrive, as tracked by `guard`.
+pub fn wry_bridge(
+    bridge: &WrySender,
+    guard: &IpcGuard,
+    webview: Entity,
+    _window: &TaoWindow,
+    request: String,
+) {
+    let request = match guard.check(request.len()) {
+        Ok(()) => serde_json::from_str(&request).unwrap_or_else(|err| {
+            let error = err.to_string();
+            Request::Violation(IpcViolationKind::Malformed { error })
+        }),
+        Err(Some(violation)) => Request::Violation(violation),
+        Err(None) => return,
+    };
+    // Bevy is gone when the app is closing, the message doesn't matter anymore.
+    let _ = bridge.send((webview, Some(guard.origin()), request));
 }
 
-pub fn bevy_emit_events_system(webview: NonSend<WryWebview>, mut events: EventReader<Event>) {
+pub fn bevy_emit_events_system(
+    webviews: NonSend<WryWebviews>,
+    mut outbox: ResMut<Outbox>,
+    mut events: EventReader<Event>,
+) {
     for event in events.iter() {
-        event.to_wry(&webview);
+        for (webview, _) in webviews.iter() {
+            outbox.send_script(webview, event.command());
+        }
     }
 }
 
 pub fn bevy_read_requests_system(world: &mut World) {
     let receiver_resource: BevyReceiver = world.remove_non_send_resource().unwrap();
-    for request in receiver_resource.0.try_iter() {
+    for (webview, origin, request) in receiver_resource.0.try_iter() {
+        if let Err(violation) = ipc_policy::check(world, webview, origin.as_deref(), &request) {
+            ipc_policy::report(world, webview, violation);
+            continue;
+        }
         match request {
-            Request::SpawnNewLinks(links) => {
-                info!("Got request: SpawnNewLinks");
-                world.send_event(NewPage { links })
+            Request::InteractiveRegions { regions } => {
+                let regions = regions.into_iter().map(Region::into_rect).collect();
+                if let Some(mut entity) = world.get_entity_mut(webview) {
+                    entity.insert(InteractiveRegions(regions));
+                }
+            }
+            Request::Input { input } => forward_input::forward(world, webview, input),
+            Request::Dirty => offscreen::mark_dirty(world, webview),
+            Request::Inspector { id, op } => inspector::handle(world, webview, id, op),
+            Request::Command { id, command } => web_commands::queue(world, webview, id, command),
+            Request::Subscribe { id, store } => {
+                web_sync::handle_subscribe(world, webview, id, &store)
+            }
+            Request::Error { message, source, line } => {
+                world.send_event(WebViewError { webview, message, source, line })
+            }
+            Request::DomMatches { watch, added, changed, removed } => {
+                dom_watch::emit(world, webview, watch, added, changed, removed)
+            }
+            Request::Console { level, message } => {
+                web_log::forward_console(webview, level, &message)
             }
+            Request::Navigation(navigation) => navigation::emit(world, webview, navigation),
+            Request::WebProcess(kind) => web_process::emit(world, webview, kind),
+            Request::Violation(violation) => ipc_policy::report(world, webview, violation),
         }
     }
     world.insert_non_send_resource(receiver_resource);
@@ -0,0 +1,363 @@
+//! Watch the elements matching CSS selectors in webviews.
+//!
+//! A [`DomWatch`] names a selector and what to extract from the matching elements:
+//! attributes, text and bounding rect. The [`DomWatches`] of a webview are installed
+//! in each page it loads, where a `MutationObserver` keeps looking for matches. They
+//! are sent to bevy as [`DomMatchAdded`], [`DomMatchChanged`] and [`DomMatchRemoved`]
+//! events, so elements rendered after the page loaded aren't missed.
+//!
+//! ```ignore
+//! DomWatches::default().with(DomWatch::new("links", "a[href]").attribute("href"))
+//! ```
+use std::collections::HashMap as StdHashMap;
+
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::navigation::PageLoaded;
+use crate::outbox::{FlushOutbox, Outbox};
+use crate::overlay::Region;
+
+/// Initialization script defining `window.__bevy_watch_dom`, it must run after
+/// [`crate::bridge::BRIDGE_SCRIPT`].
+///
+/// Installing the same watches again keeps their matches, a watch that changed or
+/// isn't installed anymore reports its matches as removed.
+pub const DOM_WATCH_SCRIPT: &str = r#"(() => {
+    const ids = new WeakMap();
+    let next_id = 0;
+    const watchers = new Map();
+    function extract(element, spec) {
+        const attributes = {};
+        for (const name of spec.attributes) {
+            // Properties have the resolved urls of `href` and `src`.
+            const value = typeof element[name] === "string" ? element[name] : element.getAttribute(name);
+            if (value !== null) {
+                attributes[name] = value;
+            }
+        }
+        let rect = null;
+        if (spec.rect) {
+            const { x, y, width, height } = element.getBoundingClientRect();
+            rect = { x, y, width, height };
+        }
+        return { attributes, text: spec.text ? element.textContent : null, rect };
+    }
+    function watch(spec) {
+        const matches = new Map();
+        const send = (added, changed, removed) => {
+            if (added.length || changed.length || removed.length) {
+                bevy.send({ type: "DomMatches", watch: spec.name, added, changed, removed });
+            }
+        };
+        const scan = () => {
+            const added = [];
+            const changed = [];
+            const seen = new Set();
+            for (const element of document.querySelectorAll(spec.selector)) {
+                if (!ids.has(element)) {
+                    ids.set(element, next_id++);
+                }
+                const id = ids.get(element);
+                const data = extract(element, spec);
+                const json = JSON.stringify(data);
+                if (!matches.has(id)) {
+                    added.push({ id, ...data });
+                } else if (matches.get(id) !== json) {
+                    changed.push({ id, ...data });
+                }
+                matches.set(id, json);
+                seen.add(id);
+            }
+            const removed = [...matches.keys()].filter((id) => !seen.has(id));
+            removed.forEach((id) => matches.delete(id));
+            send(added, changed, removed);
+        };
+        let scheduled = null;
+        const schedule = () => {
+            scheduled ??= setTimeout(() => {
+                scheduled = null;
+                scan();
+            });
+        };
+        const observer = new MutationObserver(schedule);
+        observer.observe(document, { subtree: true, childList: true, attributes: true, characterData: true });
+        if (spec.rect) {
+            window.addEventListener("scroll", schedule, true);
+            window.addEventListener("resize", schedule);
+        }
+        scan();
+        return {
+            key: JSON.stringify(spec),
+            stop() {
+                observer.disconnect();
+                clearTimeout(scheduled);
+                window.removeEventListener("scroll", schedule, true);
+                window.removeEventListener("resize", schedule);
+                send([], [], [...matches.keys()]);
+            },
+        };
+    }
+    window.__bevy_watch_dom = (specs) => {
+        const wanted = new Map(specs.map((spec) => [spec.name, spec]));
+        for (const [name, watcher] of watchers) {
+            if (watcher.key !== JSON.stringify(wanted.get(name))) {
+                watcher.stop();
+                watchers.delete(name);
+            }
+        }
+        for (const [name, spec] of wanted) {
+            if (!watchers.has(name)) {
+                watchers.set(name, watch(spec));
+            }
+        }
+    };
+})();"#;
+
+/// Elements to watch in a page, and what to extract from them.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DomWatch {
+    /// Identifies the watch in the [`DomMatchAdded`] and other events.
+    pub name: String,
+    pub selector: String,
+    /// Attributes to extract. When the element has a string property of the same
+    /// name, such as `href`, the property is extracted instead, to get absolute urls.
+    pub attributes: Vec<String>,
+    /// Extract the `textContent` of the element.
+    pub text: bool,
+    /// Extract the bounding rect of the element, in CSS pixels.
+    pub rect: bool,
+}
+impl DomWatch {
+    pub fn new(name: impl Into<String>, selector: impl Into<String>) -> Self {
+        DomWatch {
+            name: name.into(),
+            selector: selector.into(),
+            attributes: Vec::new(),
+            text: false,
+            rect: false,
+        }
+    }
+    pub fn attribute(mut self, attribute: impl Into<String>) -> Self {
+        self.attributes.push(attribute.into());
+        self
+    }
+    pub fn text(mut self) -> Self {
+        self.text = true;
+        self
+    }
+    pub fn rect(mut self) -> Self {
+        self.rect = true;
+        self
+    }
+}
+
+/// The [`DomWatch`]es of the webview of this entity.
+#[derive(Component, Debug, Clone, Default)]
+pub struct DomWatches(pub Vec<DomWatch>);
+impl DomWatches {
+    pub fn with(mut self, watch: DomWatch) -> Self {
+        self.0.push(watch);
+        self
+    }
+    /// Whether there is a watch called `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.iter().any(|watch| watch.name == name)
+    }
+}
+
+/// What was extracted from an element, see [`DomWatch`].
+#[derive(Deserialize, Debug, Clone, TS)]
+pub struct DomMatch {
+    /// Identifies the element within its page.
+    #[ts(type = "number")]
+    pub id: u64,
+    pub attributes: StdHashMap<String, String>,
+    pub text: Option<String>,
+    pub rect: Option<Region>,
+}
+
+/// An element started matching the `watch` of `webview`.
+#[derive(Event, Debug, Clone)]
+pub struct DomMatchAdded {
+    pub webview: Entity,
+    pub watch: String,
+    pub element: DomMatch,
+}
+
+/// What was extracted from an element matching the `watch` of `webview` changed.
+#[derive(Event, Debug, Clone)]
+pub struct DomMatchChanged {
+    pub webview: Entity,
+    pub watch: String,
+    pub element: DomMatch,
+}
+
+/// The element `id` doesn't match the `watch` of `webview` anymore, or its page is gone.
+#[derive(Event, Debug, Clone)]
+pub struct DomMatchRemoved {
+    pub webview: Entity,
+    pub watch: String,
+    pub id: u64,
+}
+
+/// The ids of the current matches of each watch, to remove them when the page goes away.
+///
+/// Ids restart from 0 in each page, so these are cleared when a new page is committed,
+/// before the messages of that page are read, see [`remove_page_matches`].
+#[derive(Component, Default)]
+struct CurrentMatches(HashMap<String, HashSet<u64>>);
+impl CurrentMatches {
+    /// Remove the matches of the watches not passing `keep`, as events of `webview`.
+    fn remove(&mut self, webview: Entity, keep: impl Fn(&str) -> bool) -> Vec<DomMatchRemoved> {
+        let mut removed = Vec::new();
+        self.0.retain(|watch, ids| {
+            if keep(watch) {
+                return true;
+            }
+            removed.extend(ids.drain().map(|id| DomMatchRemoved {
+                webview,
+                watch: watch.clone(),
+                id,
+            }));
+            false
+        });
+        removed
+    }
+}
+
+pub struct DomWatchPlugin;
+impl Plugin for DomWatchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DomMatchAdded>()
+            .add_event::<DomMatchChanged>()
+            .add_event::<DomMatchRemoved>()
+            .add_systems(Last, install_watches.before(FlushOutbox));
+    }
+}
+
+/// Send the events of a `DomMatches` request of `webview`.
+///
+/// Requests for watches that aren't in the [`DomWatches`] of the webview are dropped,
+/// pages can't make up matches.
+pub fn emit(
+    world: &mut World,
+    webview: Entity,
+    watch: String,
+    added: Vec<DomMatch>,
+    changed: Vec<DomMatch>,
+    removed: Vec<u64>,
+) {
+    let Some(mut entity) = world.get_entity_mut(webview) else {
+        return;
+    };
+    let watches = entity.get::<DomWatches>();
+    if !watches.map_or(false, |watches| watches.contains(&watch)) {
+        debug!("Dropped the matches of {webview:?} for the unknown watch {watch:?}");
+        return;
+    }
+    if !entity.contains::<CurrentMatches>() {
+        entity.insert(CurrentMatches::default());
+    }
+    let mut current = entity.get_mut::<CurrentMatches>().unwrap();
+    let ids = current.0.entry(watch.clone()).or_default();
+    ids.extend(added.iter().map(|element| element.id));
+    for id in &removed {
+        ids.remove(id);
+    }
+
+    for element in added {
+        let watch = watch.clone();
+        world.send_event(DomMatchAdded { webview, watch, element });
+    }
+    for element in changed {
+        let watch = watch.clone();
+        world.send_event(DomMatchChanged { webview, watch, element });
+    }
+    for id in removed {
+        let watch = watch.clone();
+        world.send_event(DomMatchRemoved { webview, watch, id });
+    }
+}
+
+/// Remove the matches of the page `webview` navigates away from.
+///
+/// Called by [`crate::navigation::emit`] when the new page is committed, in order with
+/// the `DomMatches` requests of the pages. Navigations that don't replace the page,
+/// such as fragment links, downloads or failed loads, keep its matches.
+pub fn remove_page_matches(world: &mut World, webview: Entity) {
+    let Some(mut current) = world.get_mut::<CurrentMatches>(webview) else {
+        return;
+    };
+    for removed in current.remove(webview, |_| false) {
+        world.send_event(removed);
+    }
+}
+
+/// Install the watches in new pages, and when they change.
+///
+/// Once a watch is gone, the page doesn't report its matches anymore, so they are
+/// removed here.
+fn install_watches(
+    mut loaded: EventReader<PageLoaded>,
+    watches: Query<(Entity, Ref<DomWatches>)>,
+    mut uninstalled: RemovedComponents<DomWatches>,
+    mut matches: Query<&mut CurrentMatches>,
+    mut removed: EventWriter<DomMatchRemoved>,
+    mut outbox: ResMut<Outbox>,
+) {
+    let loaded: HashSet<Entity> = loaded.iter().map(|loaded| loaded.webview).collect();
+    for (webview, watches) in &watches {
+        if !watches.is_changed() && !loaded.contains(&webview) {
+            continue;
+        }
+        if let Ok(mut current) = matches.get_mut(webview) {
+            removed.send_batch(current.remove(webview, |watch| watches.contains(watch)));
+        }
+        let specs = serde_json::to_string(&watches.0).unwrap();
+        outbox.send_script(webview, format!("window.__bevy_watch_dom?.({specs})"));
+    }
+    for webview in uninstalled.iter() {
+        if watches.contains(webview) {
+            continue;
+        }
+        if let Ok(mut current) = matches.get_mut(webview) {
+            removed.send_batch(current.remove(webview, |_| false));
+        }
+        outbox.send_script(webview, "window.__bevy_watch_dom?.([])".to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use bevy::utils::{HashMap, HashSet};
+
+    use super::CurrentMatches;
+
+    #[test]
+    fn remove_unkept_watches() {
+        let webview = Entity::from_raw(0);
+        let ids = |ids: &[u64]| ids.iter().copied().collect::<HashSet<_>>();
+        let mut current = CurrentMatches(HashMap::from_iter([
+            ("links".to_string(), ids(&[0, 1])),
+            ("buttons".to_string(), ids(&[2])),
+        ]));
+
+        let removed = current.remove(webview, |watch| watch == "links");
+        assert_eq!(removed.len(), 1);
+        assert_eq!((removed[0].watch.as_str(), removed[0].id), ("buttons", 2));
+        assert!(current.0.contains_key("links"));
+
+        let mut removed: Vec<u64> = current
+            .remove(webview, |_| false)
+            .iter()
+            .map(|r| r.id)
+            .collect();
+        removed.sort();
+        assert_eq!(removed, [0, 1]);
+        assert!(current.0.is_empty());
+    }
+}
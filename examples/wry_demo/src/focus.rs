@@ -0,0 +1,145 @@
+//! Keyboard focus between the bevy surface and webviews.
+//!
+//! `Window::focused` tells whether a bevy window or one of its webviews has focus,
+//! [`FocusOwner`] tells which one.
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy_winit_gtk::WinitWindows;
+
+use crate::webview::{FollowWindow, WryWebviews};
+
+/// What has keyboard focus within a bevy window: the bevy surface or one of its webviews.
+///
+/// This is added to all windows, and keeps its value while the window isn't focused.
+/// Send [`MoveFocus`] to change it.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FocusOwner {
+    /// The bevy render surface.
+    #[default]
+    Surface,
+    /// The webview of this entity.
+    WebView(Entity),
+}
+
+/// The [`FocusOwner`] of `window` changed.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct FocusOwnerChanged {
+    pub window: Entity,
+    pub owner: FocusOwner,
+}
+
+/// Move keyboard focus within `window` to `to`.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct MoveFocus {
+    pub window: Entity,
+    pub to: FocusOwner,
+}
+
+pub struct FocusPlugin;
+impl Plugin for FocusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FocusOwnerChanged>()
+            .add_event::<MoveFocus>()
+            .add_systems(PreUpdate, escape_returns_focus)
+            .add_systems(
+                Last,
+                (add_focus_owner, move_focus, track_focus_owner).chain(),
+            );
+    }
+}
+
+fn add_focus_owner(
+    mut commands: Commands,
+    windows: Query<Entity, (With<Window>, Without<FocusOwner>)>,
+) {
+    for entity in &windows {
+        commands.entity(entity).insert(FocusOwner::default());
+    }
+}
+
+/// Pressing Escape while a webview has focus gives focus back to the bevy surface.
+///
+/// The webview must forward Escape, see [`ForwardInput::escape_and_function_keys`].
+///
+/// [`ForwardInput::escape_and_function_keys`]: crate::forward_input::ForwardInput::escape_and_function_keys
+fn escape_returns_focus(
+    mut keys: EventReader<KeyboardInput>,
+    owners: Query<&FocusOwner>,
+    mut move_focus: EventWriter<MoveFocus>,
+) {
+    let escapes = keys
+        .iter()
+        .filter(|key| key.key_code == Some(KeyCode::Escape) && key.state == ButtonState::Pressed);
+    for escape in escapes {
+        if let Ok(FocusOwner::WebView(_)) = owners.get(escape.window) {
+            move_focus.send(MoveFocus { window: escape.window, to: FocusOwner::Surface });
+        }
+    }
+}
+
+fn move_focus(
+    mut requests: EventReader<MoveFocus>,
+    followers: Query<&FollowWindow>,
+    webviews: NonSend<WryWebviews>,
+    winit_windows: NonSend<WinitWindows>,
+) {
+    for request in requests.iter() {
+        match request.to {
+            FocusOwner::Surface => {
+                if let Some(window) = winit_windows.get_window(request.window) {
+                    window.focus_window();
+                }
+            }
+            FocusOwner::WebView(entity) => {
+                if followers.get(entity).ok().map(|f| f.0) != Some(request.window) {
+                    warn!(
+                        "Can't focus {entity:?}: it is not a webview of {:?}",
+                        request.window
+                    );
+                    continue;
+                }
+                if let Some(webview) = webviews.get(entity) {
+                    webview.window().focus_window();
+                    webview.focus();
+                }
+            }
+        }
+    }
+}
+
+/// Update [`FocusOwner`] based on which of the window or its webviews has focus.
+fn track_focus_owner(
+    mut windows: Query<(Entity, &Window, &mut FocusOwner)>,
+    followers: Query<(Entity, &FollowWindow)>,
+    webviews: NonSend<WryWebviews>,
+    winit_windows: NonSend<WinitWindows>,
+    mut changes: EventWriter<FocusOwnerChanged>,
+) {
+    for (entity, window, mut owner) in &mut windows {
+        if !window.focused {
+            continue;
+        }
+        let focused_webview = followers
+            .iter()
+            .filter(|(_, follow)| follow.0 == entity)
+            .find(|(webview, _)| {
+                webviews
+                    .get(*webview)
+                    .map_or(false, |w| w.window().has_focus())
+            });
+        let surface_focused = winit_windows
+            .get_window(entity)
+            .map_or(false, |w| w.has_focus());
+
+        let new_owner = match focused_webview {
+            Some((webview, _)) => FocusOwner::WebView(webview),
+            None if surface_focused => FocusOwner::Surface,
+            None => continue,
+        };
+        if *owner != new_owner {
+            *owner = new_owner;
+            changes.send(FocusOwnerChanged { window: entity, owner: new_owner });
+        }
+    }
+}
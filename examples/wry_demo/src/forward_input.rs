@@ -0,0 +1,340 @@
+//! Forward DOM input events to bevy.
+//!
+//! A focused webview gets all keyboard and pointer input, so bevy never sees it.
+//! With [`ForwardInput`] and [`FORWARD_INPUT_SCRIPT`], the events the page didn't
+//! handle are sent back to bevy as input events of the host window.
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::mouse::{MouseButtonInput, MouseScrollUnit, MouseWheel};
+use bevy::input::ButtonState;
+use bevy::math::DVec2;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy::window::CursorMoved;
+use serde::Deserialize;
+use ts_rs::TS;
+
+use crate::webview::{FollowWindow, WebViewZoom};
+
+/// Initialization script posting DOM input events to bevy.
+///
+/// An event is marked as handled when the page called `preventDefault` on it,
+/// or when it is a key event targeting an editable element. Pointer moves are
+/// coalesced to one per animation frame, posted before any later event.
+pub const FORWARD_INPUT_SCRIPT: &str = r#"(() => {
+    function editable(target) {
+        return target instanceof HTMLInputElement
+            || target instanceof HTMLTextAreaElement
+            || target instanceof HTMLSelectElement
+            || (target instanceof HTMLElement && target.isContentEditable);
+    }
+    let pending_move = null;
+    function flush_move() {
+        if (pending_move !== null) {
+            window.ipc.postMessage(JSON.stringify({ type: "Input", input: pending_move }));
+            pending_move = null;
+        }
+    }
+    function post(input) {
+        flush_move();
+        window.ipc.postMessage(JSON.stringify({ type: "Input", input }));
+    }
+    function key(state) {
+        return (event) => post({
+            type: "Key", state, code: event.code,
+            handled: event.defaultPrevented || editable(event.target),
+        });
+    }
+    function button(state) {
+        return (event) => post({
+            type: "Button", state, button: event.button, handled: event.defaultPrevented,
+        });
+    }
+    window.addEventListener("keydown", key("Pressed"));
+    window.addEventListener("keyup", key("Released"));
+    window.addEventListener("pointerdown", button("Pressed"));
+    window.addEventListener("pointerup", button("Released"));
+    window.addEventListener("pointermove", (event) => {
+        if (pending_move === null) {
+            requestAnimationFrame(flush_move);
+        }
+        pending_move = {
+            type: "Move", x: event.clientX, y: event.clientY, handled: event.defaultPrevented,
+        };
+    });
+    window.addEventListener("wheel", (event) => post({
+        type: "Wheel", x: -event.deltaX, y: -event.deltaY, line: event.deltaMode !== 0,
+        handled: event.defaultPrevented,
+    }));
+})();"#;
+
+/// Send the DOM input events of this webview to bevy, as input of the window it follows.
+///
+/// The webview must be built with [`FORWARD_INPUT_SCRIPT`].
+#[derive(Component, Debug, Clone)]
+pub struct ForwardInput {
+    /// Forward keyboard events the page didn't handle.
+    pub keyboard: bool,
+    /// Forward mouse button, cursor and wheel events the page didn't handle.
+    pub pointer: bool,
+    /// Keys forwarded even when the page handled them.
+    pub always_forward: HashSet<KeyCode>,
+}
+impl Default for ForwardInput {
+    fn default() -> Self {
+        ForwardInput {
+            keyboard: true,
+            pointer: true,
+            always_forward: HashSet::new(),
+        }
+    }
+}
+impl ForwardInput {
+    /// Always forward `keys`, even when the page handled them.
+    pub fn with_always_forward(mut self, keys: impl IntoIterator<Item = KeyCode>) -> Self {
+        self.always_forward.extend(keys);
+        self
+    }
+    /// Always forward Escape and the F1 to F12 keys.
+    pub fn escape_and_function_keys() -> Self {
+        use KeyCode::*;
+        ForwardInput::default()
+            .with_always_forward([Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12])
+    }
+}
+
+/// A DOM input event, as posted by [`FORWARD_INPUT_SCRIPT`].
+#[derive(Deserialize, Debug, TS)]
+#[serde(tag = "type")]
+pub enum DomInput {
+    Key {
+        state: DomButtonState,
+        code: String,
+        handled: bool,
+    },
+    Button {
+        state: DomButtonState,
+        button: i16,
+        handled: bool,
+    },
+    /// Cursor position in CSS pixels, relative to the viewport.
+    Move { x: f64, y: f64, handled: bool },
+    Wheel {
+        x: f32,
+        y: f32,
+        line: bool,
+        handled: bool,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, TS)]
+pub enum DomButtonState {
+    Pressed,
+    Released,
+}
+impl From<DomButtonState> for ButtonState {
+    fn from(state: DomButtonState) -> Self {
+        match state {
+            DomButtonState::Pressed => ButtonState::Pressed,
+            DomButtonState::Released => ButtonState::Released,
+        }
+    }
+}
+
+/// Send `input` from `webview` as a bevy input event, if its [`ForwardInput`] allows it.
+pub fn forward(world: &mut World, webview: Entity, input: DomInput) {
+    let Some(entity) = world.get_entity(webview) else {
+        return;
+    };
+    let (Some(config), Some(follow)) = (entity.get::<ForwardInput>(), entity.get::<FollowWindow>())
+    else {
+        return;
+    };
+    let zoom = entity.get::<WebViewZoom>().map_or(1.0, |zoom| zoom.0);
+    let window = follow.0;
+    match input {
+        DomInput::Key { state, code, handled } => {
+            let key_code = convert_dom_code(&code);
+            let always = key_code.map_or(false, |key| config.always_forward.contains(&key));
+            if config.keyboard && (!handled || always) {
+                let state = state.into();
+                world.send_event(KeyboardInput { scan_code: 0, key_code, state, window });
+            }
+        }
+        DomInput::Button { state, button, handled } if config.pointer && !handled => {
+            let Some(button) = convert_dom_button(button) else {
+                return;
+            };
+            let state = state.into();
+            world.send_event(MouseButtonInput { button, state, window });
+        }
+        DomInput::Move { x, y, handled } if config.pointer && !handled => {
+            let Some(mut host) = world.get_mut::<Window>(window) else {
+                return;
+            };
+            // CSS pixels are zoomed to logical pixels of the webview window, which
+            // covers the host window's content area.
+            let base_scale_factor = host.resolution.base_scale_factor();
+            let physical_position = DVec2::new(x, y) * zoom * base_scale_factor;
+            host.set_physical_cursor_position(Some(physical_position));
+            let position = (physical_position / host.resolution.scale_factor()).as_vec2();
+            world.send_event(CursorMoved { window, position });
+        }
+        DomInput::Wheel { x, y, line, handled } if config.pointer && !handled => {
+            let unit = if line { MouseScrollUnit::Line } else { MouseScrollUnit::Pixel };
+            world.send_event(MouseWheel { unit, x, y, window });
+        }
+        DomInput::Button { .. } | DomInput::Move { .. } | DomInput::Wheel { .. } => {}
+    }
+}
+
+/// The bevy button of a [`MouseEvent.button`], `None` for the negative values of
+/// events not caused by a button.
+///
+/// [`MouseEvent.button`]: https://developer.mozilla.org/en-US/docs/Web/API/MouseEvent/button
+fn convert_dom_button(button: i16) -> Option<MouseButton> {
+    match button {
+        0 => Some(MouseButton::Left),
+        1 => Some(MouseButton::Middle),
+        2 => Some(MouseButton::Right),
+        other => u16::try_from(other).ok().map(MouseButton::Other),
+    }
+}
+
+/// [`KeyboardEvent.code`] values and the corresponding bevy [`KeyCode`].
+///
+/// [`KeyboardEvent.code`]: https://developer.mozilla.org/en-US/docs/Web/API/KeyboardEvent/code
+const DOM_CODES: &[(&str, KeyCode)] = {
+    use KeyCode::*;
+    &[
+        ("KeyA", A),
+        ("KeyB", B),
+        ("KeyC", C),
+        ("KeyD", D),
+        ("KeyE", E),
+        ("KeyF", F),
+        ("KeyG", G),
+        ("KeyH", H),
+        ("KeyI", I),
+        ("KeyJ", J),
+        ("KeyK", K),
+        ("KeyL", L),
+        ("KeyM", M),
+        ("KeyN", N),
+        ("KeyO", O),
+        ("KeyP", P),
+        ("KeyQ", Q),
+        ("KeyR", R),
+        ("KeyS", S),
+        ("KeyT", T),
+        ("KeyU", U),
+        ("KeyV", V),
+        ("KeyW", W),
+        ("KeyX", X),
+        ("KeyY", Y),
+        ("KeyZ", Z),
+        ("Digit0", Key0),
+        ("Digit1", Key1),
+        ("Digit2", Key2),
+        ("Digit3", Key3),
+        ("Digit4", Key4),
+        ("Digit5", Key5),
+        ("Digit6", Key6),
+        ("Digit7", Key7),
+        ("Digit8", Key8),
+        ("Digit9", Key9),
+        ("Numpad0", Numpad0),
+        ("Numpad1", Numpad1),
+        ("Numpad2", Numpad2),
+        ("Numpad3", Numpad3),
+        ("Numpad4", Numpad4),
+        ("Numpad5", Numpad5),
+        ("Numpad6", Numpad6),
+        ("Numpad7", Numpad7),
+        ("Numpad8", Numpad8),
+        ("Numpad9", Numpad9),
+        ("NumpadAdd", NumpadAdd),
+        ("NumpadSubtract", NumpadSubtract),
+        ("NumpadMultiply", NumpadMultiply),
+        ("NumpadDivide", NumpadDivide),
+        ("NumpadDecimal", NumpadDecimal),
+        ("NumpadEnter", NumpadEnter),
+        ("F1", F1),
+        ("F2", F2),
+        ("F3", F3),
+        ("F4", F4),
+        ("F5", F5),
+        ("F6", F6),
+        ("F7", F7),
+        ("F8", F8),
+        ("F9", F9),
+        ("F10", F10),
+        ("F11", F11),
+        ("F12", F12),
+        ("Escape", Escape),
+        ("Space", Space),
+        ("Enter", Return),
+        ("Tab", Tab),
+        ("Backspace", Back),
+        ("Delete", Delete),
+        ("Insert", Insert),
+        ("Home", Home),
+        ("End", End),
+        ("PageUp", PageUp),
+        ("PageDown", PageDown),
+        ("ArrowUp", Up),
+        ("ArrowDown", Down),
+        ("ArrowLeft", Left),
+        ("ArrowRight", Right),
+        ("ShiftLeft", ShiftLeft),
+        ("ShiftRight", ShiftRight),
+        ("ControlLeft", ControlLeft),
+        ("ControlRight", ControlRight),
+        ("AltLeft", AltLeft),
+        ("AltRight", AltRight),
+        ("MetaLeft", SuperLeft),
+        ("MetaRight", SuperRight),
+        ("CapsLock", Capital),
+        ("Minus", Minus),
+        ("Equal", Equals),
+        ("BracketLeft", BracketLeft),
+        ("BracketRight", BracketRight),
+        ("Backslash", Backslash),
+        ("Semicolon", Semicolon),
+        ("Quote", Apostrophe),
+        ("Backquote", Grave),
+        ("Comma", Comma),
+        ("Period", Period),
+        ("Slash", Slash),
+    ]
+};
+
+/// Convert a `KeyboardEvent.code` to a bevy [`KeyCode`].
+fn convert_dom_code(code: &str) -> Option<KeyCode> {
+    DOM_CODES
+        .iter()
+        .find(|(dom, _)| *dom == code)
+        .map(|(_, key)| *key)
+}
+
+/// Convert a bevy [`KeyCode`] to a `KeyboardEvent.code`.
+pub fn convert_key_code(key: KeyCode) -> Option<&'static str> {
+    DOM_CODES
+        .iter()
+        .find(|(_, bevy)| *bevy == key)
+        .map(|(dom, _)| *dom)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::mouse::MouseButton;
+
+    use super::convert_dom_button;
+
+    #[test]
+    fn dom_buttons() {
+        assert_eq!(convert_dom_button(0), Some(MouseButton::Left));
+        assert_eq!(convert_dom_button(2), Some(MouseButton::Right));
+        assert_eq!(convert_dom_button(4), Some(MouseButton::Other(4)));
+        assert_eq!(convert_dom_button(-1), None);
+    }
+}
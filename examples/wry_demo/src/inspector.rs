@@ -0,0 +1,312 @@
+//! An ECS inspector for web UIs, over the bridge.
+//!
+//! Webviews with [`InspectorAccess`] can list entities, read the components of an
+//! entity as JSON, edit them, and watch entities to get their changes every frame.
+//! Components go through `bevy_reflect`, so only components registered in the
+//! `AppTypeRegistry` with `#[reflect(Component)]` are visible, and those that can't
+//! be serialized are left out.
+//!
+//! From JS, with the `bevy.inspector` object defined by [`INSPECTOR_SCRIPT`]:
+//!
+//! ```js
+//! const entities = await bevy.inspector.entities(); // [{ entity, name, parent }]
+//! const components = await bevy.inspector.components(entity); // { "type::Name": value }
+//! await bevy.inspector.set(entity, "bevy_transform::components::transform::Transform", value);
+//! const unwatch = await bevy.inspector.watch(entity, ({ changed, removed, despawned }) => {});
+//! ```
+//!
+//! Entities are identified by their `Entity::to_bits` value.
+use bevy::prelude::*;
+use bevy::reflect::serde::{TypedReflectDeserializer, TypedReflectSerializer};
+use bevy::utils::HashMap;
+use serde::de::DeserializeSeed;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use ts_rs::TS;
+
+use crate::outbox::{FlushOutbox, Outbox, PushChannel};
+use crate::typescript::CallReplies;
+use crate::webview::WryWebviews;
+
+/// Initialization script defining `bevy.inspector`, it must run after
+/// [`crate::bridge::BRIDGE_SCRIPT`].
+pub const INSPECTOR_SCRIPT: &str = r#"(() => {
+    const watchers = new Map();
+    bevy.on("inspector", (diff) => {
+        for (const callback of watchers.get(diff.entity) ?? []) {
+            callback(diff);
+        }
+    });
+    const call = (op, args = {}) => bevy.call("Inspector", { ...args, op });
+    bevy.inspector = {
+        entities: () => call("Entities"),
+        components: (entity) => call("Components", { entity }),
+        set: (entity, component, value) => call("Set", { entity, component, value }),
+        // The callback is first called with all the components of the entity.
+        async watch(entity, callback) {
+            if (!watchers.has(entity)) {
+                watchers.set(entity, new Set());
+            }
+            watchers.get(entity).add(callback);
+            const components = await call("Watch", { entity });
+            callback({ entity, changed: components, removed: [], despawned: false });
+            return () => {
+                const callbacks = watchers.get(entity);
+                callbacks?.delete(callback);
+                if (callbacks?.size === 0) {
+                    watchers.delete(entity);
+                    call("Unwatch", { entity });
+                }
+            };
+        },
+    };
+})();"#;
+
+/// Inspector calls, see the [module documentation](self).
+#[derive(Deserialize, Debug, TS)]
+#[serde(tag = "op")]
+pub enum InspectorOp {
+    /// List all entities, with their name and parent.
+    Entities,
+    /// Get the reflected components of `entity`, by type name.
+    Components {
+        #[ts(type = "number")]
+        entity: u64,
+    },
+    /// Set the value of the `component` of `entity`, by type name or short type name.
+    Set {
+        #[ts(type = "number")]
+        entity: u64,
+        component: String,
+        #[ts(type = "unknown")]
+        value: Value,
+    },
+    /// Push the changes of the components of `entity` every frame.
+    Watch {
+        #[ts(type = "number")]
+        entity: u64,
+    },
+    Unwatch {
+        #[ts(type = "number")]
+        entity: u64,
+    },
+}
+impl CallReplies for InspectorOp {
+    const TAG: &'static str = "op";
+    fn replies() -> Vec<(&'static str, String)> {
+        let components = "Record<string, unknown>".to_string();
+        vec![
+            ("Entities", Vec::<InspectorEntity>::name()),
+            ("Components", components.clone()),
+            ("Set", "null".to_string()),
+            ("Watch", components),
+            ("Unwatch", "null".to_string()),
+        ]
+    }
+}
+
+/// Allow the webview of this entity to use the inspector.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct InspectorAccess;
+
+/// Serialized components, by type name.
+type Components = Map<String, Value>;
+
+/// An entity listed by [`InspectorOp::Entities`].
+#[derive(Serialize, Debug, TS)]
+pub struct InspectorEntity {
+    #[ts(type = "number")]
+    entity: u64,
+    name: Option<String>,
+    #[ts(type = "number | null")]
+    parent: Option<u64>,
+}
+
+/// The changes of a watched entity, pushed on the `"inspector"` channel.
+#[derive(Serialize, Debug, TS)]
+pub struct InspectorDiff {
+    #[ts(type = "number")]
+    entity: u64,
+    /// The components added or changed since the last diff, by type name.
+    #[ts(type = "Record<string, unknown>")]
+    changed: Components,
+    removed: Vec<String>,
+    despawned: bool,
+}
+impl PushChannel for InspectorDiff {
+    const CHANNEL: &'static str = "inspector";
+}
+
+/// The entities watched by each webview, with the components last sent to it.
+#[derive(Resource, Default)]
+struct Watches(HashMap<Entity, HashMap<Entity, Components>>);
+
+pub struct InspectorPlugin;
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Watches>()
+            .add_systems(Last, push_changes.before(FlushOutbox));
+    }
+}
+
+/// Run the inspector call `id` from `webview`, and reply to it.
+pub fn handle(world: &mut World, webview: Entity, id: u64, op: InspectorOp) {
+    let result = if world.get::<InspectorAccess>(webview).is_some() {
+        run(world, webview, op)
+    } else {
+        Err("This webview can't use the inspector".to_string())
+    };
+    world.resource_mut::<Outbox>().reply(webview, id, result);
+}
+
+fn run(world: &mut World, webview: Entity, op: InspectorOp) -> Result<Value, String> {
+    match op {
+        InspectorOp::Entities => Ok(entities(world)),
+        InspectorOp::Components { entity } => {
+            components(world, Entity::from_bits(entity)).map(Value::Object)
+        }
+        InspectorOp::Set { entity, component, value } => {
+            set_component(world, Entity::from_bits(entity), &component, value)?;
+            Ok(Value::Null)
+        }
+        InspectorOp::Watch { entity } => {
+            let entity = Entity::from_bits(entity);
+            let components = components(world, entity)?;
+            let mut watches = world.resource_mut::<Watches>();
+            let watched = watches.0.entry(webview).or_default();
+            watched.insert(entity, components.clone());
+            Ok(Value::Object(components))
+        }
+        InspectorOp::Unwatch { entity } => {
+            let mut watches = world.resource_mut::<Watches>();
+            if let Some(watched) = watches.0.get_mut(&webview) {
+                watched.remove(&Entity::from_bits(entity));
+            }
+            Ok(Value::Null)
+        }
+    }
+}
+
+fn entities(world: &mut World) -> Value {
+    let mut query = world.query::<(Entity, Option<&Name>, Option<&Parent>)>();
+    let entities = query
+        .iter(world)
+        .map(|(entity, name, parent)| InspectorEntity {
+            entity: entity.to_bits(),
+            name: name.map(|name| name.as_str().to_string()),
+            parent: parent.map(|parent| parent.get().to_bits()),
+        });
+    json!(entities.collect::<Vec<_>>())
+}
+
+/// The reflected components of `entity`, serialized.
+fn components(world: &World, entity: Entity) -> Result<Components, String> {
+    let entity_ref = world
+        .get_entity(entity)
+        .ok_or_else(|| format!("There is no entity {entity:?}"))?;
+    let registry = world.resource::<AppTypeRegistry>().read();
+    let mut components = Components::new();
+    for component_id in entity_ref.archetype().components() {
+        let type_id = world
+            .components()
+            .get_info(component_id)
+            .and_then(|info| info.type_id());
+        let Some(registration) = type_id.and_then(|id| registry.get(id)) else {
+            continue;
+        };
+        let reflected = registration
+            .data::<ReflectComponent>()
+            .and_then(|reflect| reflect.reflect(entity_ref));
+        let Some(component) = reflected else {
+            continue;
+        };
+        let serializer = TypedReflectSerializer::new(component, &registry);
+        if let Ok(value) = serde_json::to_value(serializer) {
+            components.insert(registration.type_name().to_string(), value);
+        }
+    }
+    Ok(components)
+}
+
+fn set_component(
+    world: &mut World,
+    entity: Entity,
+    type_name: &str,
+    value: Value,
+) -> Result<(), String> {
+    let registry = world.resource::<AppTypeRegistry>().clone();
+    let registry = registry.read();
+    let registration = registry
+        .get_with_name(type_name)
+        .or_else(|| registry.get_with_short_name(type_name))
+        .ok_or_else(|| format!("Unknown type {type_name}"))?;
+    let reflect_component = registration
+        .data::<ReflectComponent>()
+        .ok_or_else(|| format!("{type_name} is not a reflected component"))?;
+    let component = TypedReflectDeserializer::new(registration, &registry)
+        .deserialize(value)
+        .map_err(|err| format!("Invalid {type_name}: {err}"))?;
+
+    let mut entity_mut = world
+        .get_entity_mut(entity)
+        .ok_or_else(|| format!("There is no entity {entity:?}"))?;
+    let mut target = reflect_component
+        .reflect_mut(&mut entity_mut)
+        .ok_or_else(|| format!("{entity:?} has no {type_name}"))?;
+    target.apply(&*component);
+    Ok(())
+}
+
+/// Send the components that changed since the last frame to the webviews watching them.
+///
+/// Watched entities are serialized every frame, so watch only what is on screen.
+fn push_changes(world: &mut World) {
+    let diffs = world.resource_scope(|world, mut watches: Mut<Watches>| {
+        let webviews = world.non_send_resource::<WryWebviews>();
+        watches
+            .0
+            .retain(|webview, _| webviews.get(*webview).is_some());
+
+        let mut diffs = Vec::new();
+        for (&webview, watched) in &mut watches.0 {
+            watched.retain(|entity, sent| {
+                let Ok(current) = components(world, *entity) else {
+                    let diff = InspectorDiff {
+                        entity: entity.to_bits(),
+                        changed: Components::new(),
+                        removed: Vec::new(),
+                        despawned: true,
+                    };
+                    diffs.push((webview, diff));
+                    return false;
+                };
+                let changed: Components = current
+                    .iter()
+                    .filter(|(name, value)| sent.get(*name) != Some(value))
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect();
+                let removed: Vec<String> = sent
+                    .keys()
+                    .filter(|name| !current.contains_key(*name))
+                    .cloned()
+                    .collect();
+                if !changed.is_empty() || !removed.is_empty() {
+                    let diff = InspectorDiff {
+                        entity: entity.to_bits(),
+                        changed,
+                        removed,
+                        despawned: false,
+                    };
+                    diffs.push((webview, diff));
+                    *sent = current;
+                }
+                true
+            });
+        }
+        diffs
+    });
+    let mut outbox = world.resource_mut::<Outbox>();
+    for (webview, diff) in diffs {
+        outbox.push(webview, &diff);
+    }
+}
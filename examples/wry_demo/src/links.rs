@@ -1,9 +1,14 @@
 //! This example displays each link to the bevy source code as a bouncing bevy-ball.
 
+use bevy::ecs::system::EntityCommands;
 use bevy::prelude::*;
 use rand::{prelude::SliceRandom, Rng};
+use serde::Serialize;
 
 use crate::bridge;
+use crate::dom_watch::{DomMatchAdded, DomMatchChanged, DomMatchRemoved, DomWatch, DomWatches};
+use crate::web_commands::WebCommandsAppExt;
+use crate::web_sync::WebSyncAppExt;
 
 pub struct LinksPlugin;
 impl Plugin for LinksPlugin {
@@ -11,7 +16,9 @@ impl Plugin for LinksPlugin {
         app.init_resource::<SelectionState>()
             .init_resource::<BevyIcon>()
             .init_resource::<LinkSelection>()
-            .add_event::<NewPage>()
+            .init_resource::<SelectedLink>()
+            .sync_resource_to_web::<SelectedLink>()
+            .add_web_event::<NewPage>()
             .add_systems(Startup, setup)
             .add_systems(
                 Update,
@@ -22,16 +29,32 @@ impl Plugin for LinksPlugin {
                     collision,
                     select_system,
                     navigate,
+                    sync_page_links,
                 ),
             );
     }
 }
 
-#[derive(Event, Debug)]
+/// Spawn a bevy-ball for each link, replacing the current ones.
+///
+/// Webviews can send it with `bevy.commands.sendEvent("NewPage", { links })`.
+#[derive(Event, Debug, Reflect)]
 pub struct NewPage {
     pub links: Vec<String>,
 }
 
+/// The name of the [`DomWatch`] of [`page_links_watch`].
+const PAGE_LINKS: &str = "links";
+
+/// Watch the links of the pages of a webview, each is shown as a bevy-ball.
+pub fn page_links_watch() -> DomWatches {
+    DomWatches::default().with(DomWatch::new(PAGE_LINKS, "a[href]").attribute("href"))
+}
+
+/// The target of the highlighted link, shown in the web UI.
+#[derive(Resource, Serialize, Default)]
+struct SelectedLink(Option<String>);
+
 #[derive(Resource, Default)]
 struct LinkSelection {
     order: Vec<Entity>,
@@ -62,6 +85,13 @@ struct Link {
     hue: f32,
 }
 
+/// A [`Link`] of an element of the page, from the `webview` watch of [`page_links_watch`].
+#[derive(Component)]
+struct PageLink {
+    webview: Entity,
+    id: u64,
+}
+
 #[derive(Component)]
 struct Velocity {
     translation: Vec3,
@@ -108,29 +138,95 @@ fn spawn_links(
     }
     link_selection.order.clear();
     for name in &new_page.links {
-        let pos = (rng.gen_range(-400.0..400.0), rng.gen_range(0.0..400.0));
-        let dir = rng.gen_range(-1.0..1.0);
-        let velocity = Vec3::new(dir * 500.0, 0.0, 0.0);
-        let hue = rng.gen_range(0.0..=360.0);
-        let transform = Transform::from_xyz(pos.0, pos.1, 0.0);
-        let target = name.clone();
-        let texture = icon.0.clone();
-        let sprite = Sprite {
-            custom_size: Some(Vec2::new(1.0, 1.0) * SPRITE_SIZE),
-            color: Color::hsla(hue, SATURATION_DESELECTED, LIGHTNESS_DESELECTED, ALPHA),
-            flip_x: rng.gen_bool(0.5),
-            ..default()
-        };
-        let entity = commands.spawn((
-            Link { target, hue },
-            Velocity { translation: velocity, rotation: -dir * 5.0 },
-            SpriteBundle { sprite, texture, transform, ..default() },
-        ));
-        link_selection.order.push(entity.id());
+        let entity = spawn_link(&mut commands, &icon, name.clone()).id();
+        link_selection.order.push(entity);
     }
     link_selection.order.shuffle(&mut rng);
 }
 
+/// Spawn a bevy-ball for the link to `target`.
+fn spawn_link<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    icon: &BevyIcon,
+    target: String,
+) -> EntityCommands<'w, 's, 'a> {
+    let mut rng = rand::thread_rng();
+    let pos = (rng.gen_range(-400.0..400.0), rng.gen_range(0.0..400.0));
+    let dir = rng.gen_range(-1.0..1.0);
+    let velocity = Vec3::new(dir * 500.0, 0.0, 0.0);
+    let hue = rng.gen_range(0.0..=360.0);
+    let transform = Transform::from_xyz(pos.0, pos.1, 0.0);
+    let texture = icon.0.clone();
+    let sprite = Sprite {
+        custom_size: Some(Vec2::new(1.0, 1.0) * SPRITE_SIZE),
+        color: Color::hsla(hue, SATURATION_DESELECTED, LIGHTNESS_DESELECTED, ALPHA),
+        flip_x: rng.gen_bool(0.5),
+        ..default()
+    };
+    commands.spawn((
+        Link { target, hue },
+        Velocity { translation: velocity, rotation: -dir * 5.0 },
+        SpriteBundle { sprite, texture, transform, ..default() },
+    ))
+}
+
+/// Keep a bevy-ball for each link of the pages, as reported by [`page_links_watch`].
+fn sync_page_links(
+    mut commands: Commands,
+    mut added: EventReader<DomMatchAdded>,
+    mut changed: EventReader<DomMatchChanged>,
+    mut removed: EventReader<DomMatchRemoved>,
+    icon: Res<BevyIcon>,
+    mut link_selection: ResMut<LinkSelection>,
+    mut links: Query<(Entity, &PageLink, &mut Link)>,
+) {
+    for DomMatchRemoved { webview, watch, id } in removed.iter() {
+        if watch != PAGE_LINKS {
+            continue;
+        }
+        let Some((entity, _, _)) = links
+            .iter()
+            .find(|(_, link, _)| link.webview == *webview && link.id == *id)
+        else {
+            continue;
+        };
+        commands.entity(entity).despawn();
+        link_selection.order.retain(|&link| link != entity);
+        if link_selection.idx >= link_selection.order.len() {
+            link_selection.idx = 0;
+        }
+    }
+    for DomMatchChanged { webview, watch, element } in changed.iter() {
+        let Some(target) = element
+            .attributes
+            .get("href")
+            .filter(|_| watch == PAGE_LINKS)
+        else {
+            continue;
+        };
+        if let Some((_, _, mut link)) = links
+            .iter_mut()
+            .find(|(_, link, _)| link.webview == *webview && link.id == element.id)
+        {
+            link.target.clone_from(target);
+        }
+    }
+    for DomMatchAdded { webview, watch, element } in added.iter() {
+        let Some(target) = element
+            .attributes
+            .get("href")
+            .filter(|_| watch == PAGE_LINKS)
+        else {
+            continue;
+        };
+        let page_link = PageLink { webview: *webview, id: element.id };
+        let entity = spawn_link(&mut commands, &icon, target.clone())
+            .insert(page_link)
+            .id();
+        link_selection.order.push(entity);
+    }
+}
+
 fn setup(mut commands: Commands) {
     commands.spawn(Camera2dBundle::default());
 
@@ -157,6 +253,7 @@ fn select_system(
     mut link_selection: ResMut<LinkSelection>,
     mut text_query: Query<&mut Text, With<LinkDisplay>>,
     mut query: Query<(&Link, &mut Sprite, &mut Transform)>,
+    mut selected: ResMut<SelectedLink>,
     time: Res<Time>,
 ) {
     let Some(&entity) = link_selection.order.get(link_selection.idx) else {
@@ -186,6 +283,7 @@ fn select_system(
     if let Ok((link, mut sprite, mut transform)) = query.get_mut(entity) {
         let mut text = text_query.single_mut();
         select(&mut sprite, link, &mut transform, &mut text);
+        selected.0 = Some(link.target.clone());
     }
 }
 
@@ -218,7 +316,9 @@ fn navigate(
         return;
     }
     info!("Sending navigation request, user pressed space");
-    let entity = link_selection.order[link_selection.idx];
+    let Some(&entity) = link_selection.order.get(link_selection.idx) else {
+        return;
+    };
     if let Ok(link) = query.get(entity) {
         let target = link.target.clone();
         events.send(bridge::Event::NavigateToPage(target));
@@ -1,26 +1,48 @@
 use bevy::prelude::*;
 use bevy::window::{PrimaryWindow, WindowResolution};
-use bevy_winit_gtk::winit_runner;
-use wry::application::dpi::{PhysicalSize, Size};
-use wry::application::event_loop::EventLoop;
-use wry::application::window::{Fullscreen, Window as TaoWindow, WindowBuilder};
+use bevy_winit_gtk::{winit_runner, EventLoopTasks, WinitWindows};
+use wry::application::event_loop::EventLoopWindowTarget;
+use wry::application::window::Window as WinitWindow;
 use wry::webview::{WebView, WebViewBuilder};
 
+mod asset_protocol;
+mod binary;
 mod bridge;
+mod dom_watch;
+mod focus;
+mod forward_input;
+mod inspector;
+mod ipc_policy;
 mod links;
+mod navigation;
+mod offscreen;
+mod outbox;
+mod overlay;
+mod typescript;
+mod ui_bundle;
+mod web_commands;
+mod web_errors;
+mod web_log;
+mod web_process;
+mod web_sync;
 // mod print_hierarchy;
-// mod webview;
-
-pub struct WryWebview(WebView);
+mod webview;
 
 fn main() {
+    let log = bevy::log::LogPlugin {
+        level: bevy::log::Level::INFO,
+        filter: "wgpu_core=warn,wgpu_hal=warn".to_string(),
+    };
+    let web_log = web_log::init_log(&log).expect("Could not set the global logger");
+    if std::env::args().any(|arg| arg == "--write-bindings") {
+        typescript::write_bindings().expect("Could not write the TypeScript bindings");
+        return;
+    }
     App::new()
         .add_plugins((
             DefaultPlugins
-                .set(bevy::log::LogPlugin {
-                    level: bevy::log::Level::INFO,
-                    filter: "wgpu_core=warn,wgpu_hal=warn".to_string(),
-                })
+                .build()
+                .set(log)
                 .set(bevy::window::WindowPlugin {
                     primary_window: Some(Window {
                         resolution: WindowResolution::new(1280., 720.),
@@ -28,11 +50,30 @@ fn main() {
                     }),
                     ..default()
                 }),
+            web_log,
             bevy_winit_gtk::WinitPlugin,
             links::LinksPlugin,
+            webview::WebViewPlugin,
+            overlay::OverlayPlugin,
+            focus::FocusPlugin,
+            asset_protocol::AssetProtocolPlugin,
+            ui_bundle::UiBundlePlugin,
+            navigation::NavigationPlugin,
+            offscreen::OffscreenPlugin,
+            inspector::InspectorPlugin,
+            web_commands::WebCommandsPlugin,
+            outbox::OutboxPlugin,
+            binary::BinaryPlugin,
+            ipc_policy::IpcPolicyPlugin,
+            web_errors::WebErrorsPlugin,
+            web_process::WebProcessPlugin,
+            dom_watch::DomWatchPlugin,
         ))
         .add_event::<bridge::Event>()
-        .add_systems(Last, (sync_window, bridge::bevy_read_requests_system))
+        .add_systems(
+            Last,
+            bridge::bevy_read_requests_system.before(outbox::FlushOutbox),
+        )
         .add_systems(PostUpdate, bridge::bevy_emit_events_system)
         .set_runner(|mut app| {
             setup_webview(&mut app);
@@ -42,43 +83,115 @@ fn main() {
 }
 fn setup_webview(app: &mut App) {
     let (bevy_receiver, wry_sender) = bridge::make_bridge();
-    let event_loop = app.world.non_send_resource::<EventLoop<()>>();
-
-    let webview_window = WindowBuilder::new()
-        .with_transparent(true)
-        .with_inner_size(PhysicalSize::new(1280., 720.))
-        .with_visible(false)
-        // .with_always_on_top(true)
-        .build(&event_loop)
-        .unwrap();
-    let webview = WebViewBuilder::new(webview_window)
-        .unwrap()
-        .with_initialization_script(
-            r#"setTimeout(() => {
-                    var links = Array.from(document.links);
-                    var to_hide = document.querySelectorAll("main, html, .layout, body");
-                    to_hide.forEach((item) => item.style = "background: transparent");
-                    window.ipc.postMessage(`NavigatedTo:${links.join(',')}`);
-                }, 2);
-                "#,
-        )
-        .with_url("https://bevyengine.org")
-        .unwrap()
-        .with_ipc_handler(move |w, s| bridge::wry_bridge(&wry_sender, w, s))
-        .with_transparent(true)
-        .build()
-        .unwrap();
+    app.insert_non_send_resource(bevy_receiver)
+        .insert_non_send_resource(wry_sender);
 
-    app.insert_non_send_resource(WryWebview(webview))
-        .insert_non_send_resource(bevy_receiver);
+    let host = app
+        .world
+        .query_filtered::<Entity, With<PrimaryWindow>>()
+        .single(&app.world);
+    let mut tasks = app.world.non_send_resource_mut::<EventLoopTasks>();
+    tasks.push(move |event_loop, world| {
+        let entity = world
+            .spawn((
+                overlay::ClickThrough,
+                forward_input::ForwardInput::escape_and_function_keys(),
+                // Remote pages open in their own window, without the inspector.
+                inspector::InspectorAccess,
+                web_commands::WebCommandPermissions::default().allow_event::<links::NewPage>(),
+                web_log::WebLogStream::new(bevy::log::Level::INFO),
+                links::page_links_watch(),
+                navigation::NavigationPolicy::stay_on(&[ui_bundle::INDEX_URL]),
+            ))
+            .id();
+        create_webview(event_loop, world, entity, host, ui_bundle::INDEX_URL);
+    });
 }
 
-fn sync_window(
-    main_window: Query<&Window, (With<PrimaryWindow>, Changed<Window>)>,
-    webview: NonSend<WryWebview>,
+/// Make `entity` a webview showing `url`, following the bevy window `host`.
+///
+/// `host` must already have its `winit` window, so this is usually called from
+/// an [`EventLoopTasks`] task.
+pub fn create_webview(
+    event_loop: &EventLoopWindowTarget<()>,
+    world: &mut World,
+    entity: Entity,
+    host: Entity,
+    url: &str,
 ) {
-    let Ok(_window) = main_window.get_single() else {
+    let winit_windows = world.non_send_resource::<WinitWindows>();
+    let Some(host_window) = winit_windows.get_window(host) else {
+        warn!("Can't create webview {entity:?}, its host window {host:?} is gone");
+        world.despawn(entity);
         return;
     };
-    let wv_window = webview.0.window();
+    let webview_window = webview::follower_window(event_loop, host_window);
+    let scripts = [
+        overlay::CLICK_THROUGH_SCRIPT,
+        forward_input::FORWARD_INPUT_SCRIPT,
+    ];
+    let webview = build_webview(world, entity, webview_window, url, &scripts);
+    webview::insert_follower(world, entity, host, webview);
+}
+
+/// Make `entity` an offscreen webview showing `url`, rendered into `offscreen.image`.
+///
+/// Call this from an [`EventLoopTasks`] task.
+pub fn create_offscreen_webview(
+    event_loop: &EventLoopWindowTarget<()>,
+    world: &mut World,
+    entity: Entity,
+    size: UVec2,
+    offscreen: offscreen::OffscreenWebView,
+    url: &str,
+) {
+    let webview_window = offscreen::offscreen_window(event_loop, size);
+    let scripts = [offscreen::OFFSCREEN_SCRIPT];
+    let webview = build_webview(world, entity, webview_window, url, &scripts);
+    offscreen::insert_offscreen(world, entity, offscreen, webview);
+}
+
+/// Build the webview of `entity` in `window`, with the bridge, navigation handlers
+/// and custom protocols set up.
+fn build_webview(
+    world: &mut World,
+    entity: Entity,
+    window: WinitWindow,
+    url: &str,
+    scripts: &[&str],
+) -> WebView {
+    let wry_sender = world.non_send_resource::<bridge::WrySender>().clone();
+
+    let guard = ipc_policy::guard(world, entity, url);
+    let builder = WebViewBuilder::new(window).unwrap();
+    let origin = guard.page_origin();
+    let mut builder = navigation::with_handlers(builder, world, entity, &wry_sender, origin)
+        .with_initialization_script(bridge::BRIDGE_SCRIPT)
+        .with_initialization_script(web_errors::ERRORS_SCRIPT)
+        .with_initialization_script(web_log::CONSOLE_SCRIPT)
+        .with_initialization_script(dom_watch::DOM_WATCH_SCRIPT)
+        .with_initialization_script(inspector::INSPECTOR_SCRIPT)
+        .with_initialization_script(web_sync::SYNC_SCRIPT)
+        .with_initialization_script(web_commands::COMMANDS_SCRIPT)
+        .with_initialization_script(binary::BUFFERS_SCRIPT);
+    for script in scripts {
+        builder = builder.with_initialization_script(script);
+    }
+    let ipc_sender = wry_sender.clone();
+    let webview = builder
+        .with_custom_protocol(
+            asset_protocol::SCHEME.to_string(),
+            asset_protocol::handler(world),
+        )
+        .with_custom_protocol(ui_bundle::SCHEME.to_string(), ui_bundle::handler(world))
+        .with_custom_protocol(binary::SCHEME.to_string(), binary::handler(world, entity))
+        .with_url(url)
+        .unwrap()
+        .with_ipc_handler(move |w, s| bridge::wry_bridge(&ipc_sender, &guard, entity, w, s))
+        .with_transparent(true)
+        .build()
+        .unwrap();
+    navigation::connect_load_failed(&webview, entity, &wry_sender);
+    web_process::connect(&webview, entity, &wry_sender);
+    webview
 }
@@ -0,0 +1,499 @@
+//! Navigation lifecycle events of webviews, and a policy deciding which navigations happen.
+//!
+//! The webview engine reports navigations from its own callbacks, outside of the
+//! bevy schedule. They are sent to bevy through the bridge, and emitted as bevy events
+//! tagged with the webview entity.
+//!
+//! Webviews are controlled with [`NavigationCommand`]s, and their navigation state is
+//! kept in [`WebViewHistory`].
+use std::sync::{Arc, RwLock};
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_winit_gtk::EventLoopTasks;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use wry::webview::{PageLoadEvent, WebViewBuilder};
+
+use crate::bridge::{Request, WrySender};
+use crate::dom_watch;
+use crate::forward_input::ForwardInput;
+use crate::ipc_policy::{origin_of, PageOrigin};
+use crate::web_errors::WebViewError;
+use crate::webview::WryWebviews;
+
+/// The webview started navigating to `url`, after [`NavigationPolicy`] allowed it.
+#[derive(Event, Debug, Clone)]
+pub struct NavigationStarted {
+    pub webview: Entity,
+    pub url: String,
+}
+
+/// The webview finished loading the page at `url`.
+#[derive(Event, Debug, Clone)]
+pub struct PageLoaded {
+    pub webview: Entity,
+    pub url: String,
+}
+
+/// The url of the webview changed, including through the history API.
+#[derive(Event, Debug, Clone)]
+pub struct UrlChanged {
+    pub webview: Entity,
+    pub url: String,
+}
+
+/// The title of the page in the webview changed.
+#[derive(Event, Debug, Clone)]
+pub struct TitleChanged {
+    pub webview: Entity,
+    pub title: String,
+}
+
+/// The page at `url` failed to load.
+///
+/// This is only reported on linux.
+#[derive(Event, Debug, Clone)]
+pub struct LoadFailed {
+    pub webview: Entity,
+    pub url: String,
+    pub error: String,
+}
+
+/// Navigation events reported by the webview engine.
+#[derive(Debug)]
+pub enum Navigation {
+    Started {
+        url: String,
+    },
+    /// The page at `url` replaced the previous one, the messages that follow come from it.
+    Committed {
+        url: String,
+    },
+    Loaded {
+        url: String,
+    },
+    TitleChanged {
+        title: String,
+    },
+    Failed {
+        url: String,
+        error: String,
+    },
+    Redirect {
+        url: String,
+    },
+    NewWindow {
+        url: String,
+    },
+}
+
+/// What to do with a navigation, see [`NavigationPolicy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NavigationDecision {
+    Allow,
+    Deny,
+    /// Cancel the navigation, and navigate to this url instead.
+    ///
+    /// The new navigation goes through the policy as well, so make sure it is allowed.
+    Redirect(String),
+    /// Cancel the navigation, and open the url as if the page asked for a new window,
+    /// according to the [`NewWindowPolicy`] of the webview.
+    NewWindow,
+}
+
+type PolicyFn = dyn Fn(&str) -> NavigationDecision + Send + Sync;
+
+/// Decides whether navigations of this webview to a url are allowed.
+///
+/// The webview engine asks for a decision synchronously, while the bevy world is not
+/// accessible. So the policy is a callback, rather than a system, and should capture
+/// whatever state it needs.
+#[derive(Component, Clone)]
+pub struct NavigationPolicy(pub Arc<PolicyFn>);
+impl NavigationPolicy {
+    pub fn new(policy: impl Fn(&str) -> NavigationDecision + Send + Sync + 'static) -> Self {
+        NavigationPolicy(Arc::new(policy))
+    }
+    /// Only allow urls starting with one of `prefixes`.
+    pub fn allow_prefixes(prefixes: &[&str]) -> Self {
+        let prefixes: Vec<String> = prefixes.iter().map(|p| p.to_string()).collect();
+        NavigationPolicy::new(move |url| {
+            if prefixes
+                .iter()
+                .any(|prefix| url.starts_with(prefix.as_str()))
+            {
+                NavigationDecision::Allow
+            } else {
+                NavigationDecision::Deny
+            }
+        })
+    }
+    /// Keep the webview on urls starting with one of `prefixes`, and open other urls
+    /// as new windows.
+    ///
+    /// Useful for webviews with privileges, such as [`InspectorAccess`], that must not
+    /// show remote pages.
+    ///
+    /// [`InspectorAccess`]: crate::inspector::InspectorAccess
+    pub fn stay_on(prefixes: &[&str]) -> Self {
+        let prefixes: Vec<String> = prefixes.iter().map(|p| p.to_string()).collect();
+        NavigationPolicy::new(move |url| {
+            if prefixes
+                .iter()
+                .any(|prefix| url.starts_with(prefix.as_str()))
+            {
+                NavigationDecision::Allow
+            } else {
+                NavigationDecision::NewWindow
+            }
+        })
+    }
+}
+
+/// What to do when the page asks for a new window, with `target="_blank"` links
+/// or `window.open`.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NewWindowPolicy {
+    /// Spawn a new bevy [`Window`] with a webview showing the page.
+    #[default]
+    NewWindow,
+    /// Show the page in the webview that asked for it.
+    SameWebView,
+    Deny,
+}
+
+/// Control the navigation of `webview`.
+#[derive(Event, Debug, Clone)]
+pub struct Navigate {
+    pub webview: Entity,
+    pub command: NavigationCommand,
+}
+
+#[derive(Debug, Clone)]
+pub enum NavigationCommand {
+    LoadUrl(String),
+    /// Show this HTML document. Relative urls in it can't be resolved.
+    LoadHtml(String),
+    Back,
+    Forward,
+    Reload,
+    /// Stop loading the current page.
+    Stop,
+}
+
+/// Navigation state of a webview, added to all webviews.
+///
+/// `can_go_back` and `can_go_forward` are only known on linux, they are always
+/// `false` on other platforms.
+#[derive(Component, Debug, Default, Clone, PartialEq, Eq)]
+pub struct WebViewHistory {
+    pub url: String,
+    pub can_go_back: bool,
+    pub can_go_forward: bool,
+    pub loading: bool,
+}
+
+type PolicySlot = Arc<RwLock<Option<Arc<PolicyFn>>>>;
+
+/// The [`NavigationPolicy`] of each webview, as seen by the navigation handlers.
+#[derive(Resource, Default)]
+struct PolicySlots(HashMap<Entity, PolicySlot>);
+
+/// The url last seen for a webview, to detect url changes.
+#[derive(Component)]
+struct LastUrl(String);
+
+pub struct NavigationPlugin;
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PolicySlots>()
+            .add_event::<NavigationStarted>()
+            .add_event::<PageLoaded>()
+            .add_event::<UrlChanged>()
+            .add_event::<TitleChanged>()
+            .add_event::<LoadFailed>()
+            .add_event::<Navigate>()
+            .add_systems(
+                Last,
+                (
+                    sync_policies,
+                    detect_url_changes,
+                    run_commands,
+                    update_history,
+                ),
+            );
+    }
+}
+
+/// Add the navigation handlers of the webview of `entity` to `builder`, they keep
+/// `origin` up to date with the committed page, see [`crate::ipc_policy::IpcGuard`].
+///
+/// On linux, also call [`connect_load_failed`] once the webview is built.
+pub fn with_handlers<'a>(
+    builder: WebViewBuilder<'a>,
+    world: &mut World,
+    entity: Entity,
+    sender: &WrySender,
+    origin: PageOrigin,
+) -> WebViewBuilder<'a> {
+    // Seeded now, the initial load and early navigations go through the policy too.
+    let policy = world.get::<NavigationPolicy>(entity).map(|p| p.0.clone());
+    let slot = PolicySlot::new(RwLock::new(policy));
+    let mut slots = world.resource_mut::<PolicySlots>();
+    slots.0.insert(entity, slot.clone());
+
+    let (navigations, loads) = (sender.clone(), sender.clone());
+    let (titles, new_windows) = (sender.clone(), sender.clone());
+    builder
+        .with_navigation_handler(move |url| {
+            let policy = slot.read().unwrap().clone();
+            let decision = policy.map_or(NavigationDecision::Allow, |policy| policy(&url));
+            let allowed = decision == NavigationDecision::Allow;
+            match decision {
+                NavigationDecision::Allow => {
+                    send(&navigations, entity, Navigation::Started { url })
+                }
+                NavigationDecision::Deny => debug!("Navigation to {url} denied"),
+                NavigationDecision::Redirect(url) => {
+                    send(&navigations, entity, Navigation::Redirect { url });
+                }
+                NavigationDecision::NewWindow => {
+                    send(&navigations, entity, Navigation::NewWindow { url })
+                }
+            }
+            allowed
+        })
+        .with_on_page_load_handler(move |event, url| match event {
+            // Started once the new page is committed, its messages come after this.
+            PageLoadEvent::Started => {
+                *origin.write().unwrap() = origin_of(&url);
+                send(&loads, entity, Navigation::Committed { url });
+            }
+            PageLoadEvent::Finished => send(&loads, entity, Navigation::Loaded { url }),
+        })
+        .with_document_title_changed_handler(move |_, title| {
+            send(&titles, entity, Navigation::TitleChanged { title });
+        })
+        // The `NewWindowPolicy` is applied by bevy, the engine never opens windows itself.
+        .with_new_window_req_handler(move |url| {
+            send(&new_windows, entity, Navigation::NewWindow { url });
+            false
+        })
+}
+
+/// Report load failures of the webview of `entity`, wry doesn't expose them.
+#[cfg(target_os = "linux")]
+pub fn connect_load_failed(webview: &wry::webview::WebView, entity: Entity, sender: &WrySender) {
+    use webkit2gtk::WebViewExt;
+    use wry::webview::WebviewExtUnix;
+
+    let sender = sender.clone();
+    webview
+        .webview()
+        .connect_load_failed(move |_, _, url, error| {
+            let (url, error) = (url.to_string(), error.to_string());
+            send(&sender, entity, Navigation::Failed { url, error });
+            false
+        });
+}
+#[cfg(not(target_os = "linux"))]
+pub fn connect_load_failed(_: &wry::webview::WebView, _: Entity, _: &WrySender) {}
+
+fn send(sender: &WrySender, entity: Entity, navigation: Navigation) {
+    // The receiver is only dropped when the app exits.
+    let _ = sender.send((entity, None, Request::Navigation(navigation)));
+}
+
+/// Emit the bevy event corresponding to `navigation`.
+pub fn emit(world: &mut World, webview: Entity, navigation: Navigation) {
+    match navigation {
+        Navigation::Started { url } => world.send_event(NavigationStarted { webview, url }),
+        Navigation::Committed { url } => {
+            debug!("{webview:?} committed {url}");
+            dom_watch::remove_page_matches(world, webview);
+        }
+        Navigation::Loaded { url } => world.send_event(PageLoaded { webview, url }),
+        Navigation::TitleChanged { title } => world.send_event(TitleChanged { webview, title }),
+        Navigation::Failed { url, error } => {
+            world.send_event(LoadFailed { webview, url, error });
+        }
+        Navigation::Redirect { url } => load_url(world, webview, &url),
+        Navigation::NewWindow { url } => {
+            let policy = world.get::<NewWindowPolicy>(webview).copied();
+            match policy.unwrap_or_default() {
+                NewWindowPolicy::NewWindow => open_window(world, url),
+                NewWindowPolicy::SameWebView => load_url(world, webview, &url),
+                NewWindowPolicy::Deny => debug!("New window for {url} denied"),
+            }
+        }
+    }
+}
+
+fn load_url(world: &World, webview: Entity, url: &str) {
+    let webviews = world.non_send_resource::<WryWebviews>();
+    if let Some(webview) = webviews.get(webview) {
+        webview.load_url(url);
+    }
+}
+
+/// Spawn a bevy window with a webview showing `url`.
+fn open_window(world: &mut World, url: String) {
+    let window = Window { title: url.clone(), ..default() };
+    let host = world.spawn(window).id();
+    let webview = world.spawn(ForwardInput::escape_and_function_keys()).id();
+
+    // The webview can only be created once the runner created the bevy window.
+    let mut tasks = world.non_send_resource_mut::<EventLoopTasks>();
+    tasks.push(move |event_loop, world| {
+        crate::create_webview(event_loop, world, webview, host, &url);
+    });
+}
+
+/// Make the navigation handlers see changes to [`NavigationPolicy`].
+fn sync_policies(
+    mut slots: ResMut<PolicySlots>,
+    policies: Query<(Entity, &NavigationPolicy), Changed<NavigationPolicy>>,
+    mut removed: RemovedComponents<NavigationPolicy>,
+    webviews: NonSend<WryWebviews>,
+) {
+    for (entity, policy) in &policies {
+        if let Some(slot) = slots.0.get(&entity) {
+            *slot.write().unwrap() = Some(policy.0.clone());
+        }
+    }
+    for entity in removed.iter() {
+        if let Some(slot) = slots.0.get(&entity) {
+            *slot.write().unwrap() = None;
+        }
+    }
+    slots.0.retain(|entity, _| webviews.get(*entity).is_some());
+}
+
+/// Send [`UrlChanged`] when the url of a webview changes.
+///
+/// The url is polled, since it also changes through the history API, which wry doesn't report.
+fn detect_url_changes(
+    mut commands: Commands,
+    last_urls: Query<&LastUrl>,
+    webviews: NonSend<WryWebviews>,
+    mut changes: EventWriter<UrlChanged>,
+) {
+    for (entity, webview) in webviews.iter() {
+        let url = webview.url().to_string();
+        if last_urls.get(entity).map_or(false, |last| last.0 == url) {
+            continue;
+        }
+        changes.send(UrlChanged { webview: entity, url: url.clone() });
+        commands.entity(entity).insert(LastUrl(url));
+    }
+}
+
+fn run_commands(
+    mut commands: EventReader<Navigate>,
+    webviews: NonSend<WryWebviews>,
+    mut errors: EventWriter<WebViewError>,
+) {
+    for Navigate { webview: entity, command } in commands.iter() {
+        let Some(webview) = webviews.get(*entity) else {
+            continue;
+        };
+        if engine_command(webview, command) {
+            continue;
+        }
+        let script = match command {
+            NavigationCommand::LoadUrl(url) => {
+                webview.load_url(url);
+                continue;
+            }
+            NavigationCommand::LoadHtml(html) => {
+                let html = utf8_percent_encode(html, NON_ALPHANUMERIC);
+                webview.load_url(&format!("data:text/html;charset=utf-8,{html}"));
+                continue;
+            }
+            NavigationCommand::Back => "window.history.back()",
+            NavigationCommand::Forward => "window.history.forward()",
+            NavigationCommand::Reload => "window.location.reload()",
+            NavigationCommand::Stop => "window.stop()",
+        };
+        if let Err(err) = webview.evaluate_script(script) {
+            errors.send(WebViewError::evaluate_script(*entity, err));
+        }
+    }
+}
+
+/// Keep [`WebViewHistory`] up to date with the webview engine.
+fn update_history(
+    mut commands: Commands,
+    mut histories: Query<&mut WebViewHistory>,
+    mut started: EventReader<NavigationStarted>,
+    mut loaded: EventReader<PageLoaded>,
+    mut failed: EventReader<LoadFailed>,
+    mut loading: Local<HashMap<Entity, bool>>,
+    webviews: NonSend<WryWebviews>,
+) {
+    // A page that started and finished loading within a frame is not loading anymore.
+    let started = started.iter().map(|e| (e.webview, true));
+    let done = loaded
+        .iter()
+        .map(|e| e.webview)
+        .chain(failed.iter().map(|e| e.webview));
+    loading.extend(started.chain(done.map(|webview| (webview, false))));
+    loading.retain(|entity, _| webviews.get(*entity).is_some());
+
+    for (entity, webview) in webviews.iter() {
+        let (can_go_back, can_go_forward) = engine_history(webview);
+        let history = WebViewHistory {
+            url: webview.url().to_string(),
+            can_go_back,
+            can_go_forward,
+            loading: loading.get(&entity).copied().unwrap_or(false),
+        };
+        match histories.get_mut(entity) {
+            Ok(mut current) => {
+                current.set_if_neq(history);
+            }
+            Err(_) => {
+                commands.entity(entity).insert(history);
+            }
+        }
+    }
+}
+
+/// Whether the webview can go back and forward in its history.
+#[cfg(target_os = "linux")]
+fn engine_history(webview: &wry::webview::WebView) -> (bool, bool) {
+    use webkit2gtk::WebViewExt;
+    use wry::webview::WebviewExtUnix;
+
+    let webview = webview.webview();
+    (webview.can_go_back(), webview.can_go_forward())
+}
+#[cfg(not(target_os = "linux"))]
+fn engine_history(_: &wry::webview::WebView) -> (bool, bool) {
+    (false, false)
+}
+
+/// Run a history command through the webview engine, returns `false` if it must
+/// run as a script instead.
+///
+/// The engine doesn't depend on the page, which may have overridden `window.history`
+/// or may not have run its scripts at all, after a crash for example.
+#[cfg(target_os = "linux")]
+fn engine_command(webview: &wry::webview::WebView, command: &NavigationCommand) -> bool {
+    use webkit2gtk::WebViewExt;
+    use wry::webview::WebviewExtUnix;
+
+    let webview = webview.webview();
+    match command {
+        NavigationCommand::Back => webview.go_back(),
+        NavigationCommand::Forward => webview.go_forward(),
+        NavigationCommand::Reload => webview.reload(),
+        NavigationCommand::Stop => webview.stop_loading(),
+        NavigationCommand::LoadUrl(_) | NavigationCommand::LoadHtml(_) => return false,
+    }
+    true
+}
+#[cfg(not(target_os = "linux"))]
+fn engine_command(_: &wry::webview::WebView, _: &NavigationCommand) -> bool {
+    false
+}
@@ -0,0 +1,451 @@
+//! Webviews rendered into a bevy [`Image`], for in-world screens.
+//!
+//! An offscreen webview still has a window, so that the webview engine renders it,
+//! but the window is fully transparent and ignores input. Its content is captured
+//! into [`OffscreenWebView::image`] at most [`OffscreenWebView::max_fps`] times a second.
+//!
+//! The game decides where the pointer is on the page, typically by casting a ray on
+//! the mesh showing the image, and sends it as [`OffscreenInput`] with UV coordinates.
+//! It is delivered to the page as synthetic DOM events, so default actions other than
+//! clicks, focus, text input and scrolling don't happen.
+//!
+//! Capturing is only supported on linux.
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy_winit_gtk::WinitWindows;
+use wry::application::dpi::PhysicalSize;
+use wry::application::event_loop::EventLoopWindowTarget;
+use wry::application::window::{Window as WinitWindow, WindowBuilder};
+use wry::webview::WebView;
+
+use serde_json::{json, Value};
+
+use crate::forward_input::convert_key_code;
+use crate::outbox::{FlushOutbox, Outbox};
+use crate::webview::WryWebviews;
+
+/// How long to wait for a capture before starting another one.
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Initialization script of offscreen webviews.
+///
+/// Reports when the page changes, and receives the input sent with [`OffscreenInput`].
+pub const OFFSCREEN_SCRIPT: &str = r#"(() => {
+    let scheduled = false;
+    function report() {
+        scheduled = false;
+        window.ipc.postMessage(JSON.stringify({ type: "Dirty" }));
+        // Running animations change the page without DOM mutations.
+        if (document.getAnimations().length > 0) {
+            schedule();
+        }
+    }
+    function schedule() {
+        if (!scheduled) {
+            scheduled = true;
+            requestAnimationFrame(report);
+        }
+    }
+    window.addEventListener("DOMContentLoaded", () => {
+        const observer = new MutationObserver(schedule);
+        observer.observe(document.documentElement, {
+            subtree: true, childList: true, attributes: true, characterData: true,
+        });
+        schedule();
+    });
+    for (const event of ["load", "resize", "scroll", "input", "focusin", "animationstart", "transitionrun"]) {
+        window.addEventListener(event, schedule, true);
+    }
+
+    function editable(target) {
+        return target instanceof HTMLInputElement
+            || target instanceof HTMLTextAreaElement
+            || (target instanceof HTMLElement && target.isContentEditable);
+    }
+    let pressed_on = null;
+    window.__bevy_offscreen = {
+        at(u, v) {
+            const x = u * window.innerWidth, y = v * window.innerHeight;
+            const target = document.elementFromPoint(x, y) ?? document.body;
+            return [target, { bubbles: true, cancelable: true, clientX: x, clientY: y }];
+        },
+        move(u, v) {
+            const [target, init] = this.at(u, v);
+            target.dispatchEvent(new PointerEvent("pointermove", init));
+            target.dispatchEvent(new MouseEvent("mousemove", init));
+        },
+        button(u, v, button, pressed) {
+            const [target, init] = this.at(u, v);
+            init.button = button;
+            const [pointer, mouse] = pressed ? ["pointerdown", "mousedown"] : ["pointerup", "mouseup"];
+            target.dispatchEvent(new PointerEvent(pointer, init));
+            if (!target.dispatchEvent(new MouseEvent(mouse, init)) || button !== 0) {
+                return;
+            }
+            if (pressed) {
+                pressed_on = target;
+                target.focus?.();
+            } else if (pressed_on === target) {
+                target.click();
+            }
+        },
+        wheel(u, v, dx, dy) {
+            const [target, init] = this.at(u, v);
+            Object.assign(init, { deltaX: dx, deltaY: dy });
+            if (target.dispatchEvent(new WheelEvent("wheel", init))) {
+                window.scrollBy(dx, dy);
+            }
+        },
+        key(code, pressed) {
+            const key = code.startsWith("Key") ? code.slice(3).toLowerCase()
+                : code.startsWith("Digit") ? code.slice(5)
+                : code === "Space" ? " " : code;
+            const target = document.activeElement ?? document.body;
+            const init = { bubbles: true, cancelable: true, code, key };
+            const event = new KeyboardEvent(pressed ? "keydown" : "keyup", init);
+            if (!target.dispatchEvent(event) || !pressed || !editable(target)) {
+                return;
+            }
+            if (code === "Backspace") {
+                document.execCommand("delete");
+            } else if (code === "Enter" && target.form) {
+                target.form.requestSubmit();
+            }
+        },
+        text(text) {
+            if (editable(document.activeElement)) {
+                document.execCommand("insertText", false, text);
+            }
+        },
+    };
+})();"#;
+
+/// A webview rendered into `image` rather than on screen.
+///
+/// Create it with [`offscreen_window`] and [`insert_offscreen`].
+#[derive(Component, Debug, Clone)]
+pub struct OffscreenWebView {
+    /// The image the page is rendered into, it is resized to the page size.
+    pub image: Handle<Image>,
+    /// Maximum number of captures per second, `None` to capture every frame.
+    ///
+    /// Values that are not strictly positive don't limit captures either.
+    pub max_fps: Option<f32>,
+    /// Only capture the page when it changed.
+    ///
+    /// Changes are detected from DOM mutations, scrolling and CSS animations, so
+    /// `<canvas>` and `<video>` content isn't captured when this is `true`.
+    pub only_when_dirty: bool,
+}
+
+/// Pointer and keyboard input sent to an offscreen webview.
+///
+/// `uv` is the position on the page, from `(0, 0)` top left to `(1, 1)` bottom right.
+#[derive(Event, Debug, Clone)]
+pub struct OffscreenInput {
+    pub webview: Entity,
+    pub input: OffscreenInputKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum OffscreenInputKind {
+    Move {
+        uv: Vec2,
+    },
+    Button {
+        uv: Vec2,
+        button: MouseButton,
+        state: ButtonState,
+    },
+    /// Scroll by `delta` pixels.
+    Wheel {
+        uv: Vec2,
+        delta: Vec2,
+    },
+    Key {
+        key_code: KeyCode,
+        state: ButtonState,
+    },
+    /// Text typed into the focused element, as from `ReceivedCharacter`.
+    Text(String),
+}
+
+/// A captured frame, in RGBA.
+struct Frame {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+/// Where the webview engine puts the frame once it is captured, or why it failed.
+type CaptureSlot = Arc<Mutex<Option<Result<Frame, String>>>>;
+
+/// Capture state of an [`OffscreenWebView`].
+#[derive(Component, Default)]
+struct Capture {
+    /// Set by the page through [`OFFSCREEN_SCRIPT`].
+    dirty: bool,
+    last_capture: Option<Instant>,
+    /// The capture in progress, with when it started.
+    pending: Option<(CaptureSlot, Instant)>,
+}
+
+pub struct OffscreenPlugin;
+impl Plugin for OffscreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<OffscreenInput>().add_systems(
+            Last,
+            (close_despawned, send_input.before(FlushOutbox), capture),
+        );
+    }
+}
+
+/// Create the window of an offscreen webview, of `size` physical pixels.
+pub fn offscreen_window(event_loop: &EventLoopWindowTarget<()>, size: UVec2) -> WinitWindow {
+    let window = WindowBuilder::new()
+        .with_transparent(true)
+        .with_decorations(false)
+        .with_inner_size(PhysicalSize::new(size.x, size.y))
+        .build(event_loop)
+        .unwrap();
+
+    // The window must be mapped for the page to be rendered, so hide it in other ways.
+    #[cfg(target_os = "linux")]
+    {
+        use gtk::cairo::Region;
+        use gtk::prelude::{GtkWindowExt, WidgetExt};
+        use wry::application::platform::unix::WindowExtUnix;
+
+        let gtk_window = window.gtk_window();
+        gtk_window.set_opacity(0.0);
+        gtk_window.set_accept_focus(false);
+        gtk_window.set_keep_below(true);
+        gtk_window.set_skip_taskbar_hint(true);
+        gtk_window.set_skip_pager_hint(true);
+        gtk_window.input_shape_combine_region(Some(&Region::create()));
+    }
+    window
+}
+
+/// Make `entity` an offscreen webview.
+///
+/// `webview` should be built from a window created with [`offscreen_window`].
+pub fn insert_offscreen(
+    world: &mut World,
+    entity: Entity,
+    offscreen: OffscreenWebView,
+    webview: WebView,
+) {
+    // Attach the window to the webview itself, so that bevy ignores its events.
+    let webview_window = webview.window().id();
+    world
+        .non_send_resource_mut::<WinitWindows>()
+        .attach_window(webview_window, entity);
+    world
+        .non_send_resource_mut::<WryWebviews>()
+        .insert(entity, webview);
+    world.entity_mut(entity).insert((
+        Name::new("Offscreen webview"),
+        offscreen,
+        Capture::default(),
+    ));
+}
+
+/// The page of `webview` changed, see [`OffscreenWebView::only_when_dirty`].
+pub fn mark_dirty(world: &mut World, webview: Entity) {
+    if let Some(mut capture) = world.get_mut::<Capture>(webview) {
+        capture.dirty = true;
+    }
+}
+
+/// Close the webviews of despawned offscreen webview entities.
+fn close_despawned(
+    mut removed: RemovedComponents<OffscreenWebView>,
+    mut webviews: NonSendMut<WryWebviews>,
+    mut winit_windows: NonSendMut<WinitWindows>,
+) {
+    for entity in removed.iter() {
+        if let Some(webview) = webviews.remove(entity) {
+            winit_windows.detach_window(webview.window().id());
+        }
+    }
+}
+
+fn capture(
+    mut offscreens: Query<(Entity, &OffscreenWebView, &mut Capture)>,
+    mut images: ResMut<Assets<Image>>,
+    webviews: NonSend<WryWebviews>,
+) {
+    let now = Instant::now();
+    for (entity, offscreen, mut capture) in &mut offscreens {
+        if let Some((slot, started)) = &capture.pending {
+            let result = slot.lock().unwrap().take();
+            let timed_out = now - *started >= CAPTURE_TIMEOUT;
+            match result {
+                Some(Ok(frame)) => update_image(&mut images, &offscreen.image, frame),
+                // Capture again, the page may not have changed since.
+                Some(Err(err)) => {
+                    warn!("Could not capture offscreen webview {entity:?}: {err}");
+                    capture.dirty = true;
+                }
+                None if timed_out => {
+                    warn!("Capturing offscreen webview {entity:?} timed out");
+                    capture.dirty = true;
+                }
+                None => continue,
+            }
+            capture.pending = None;
+        }
+        let interval = offscreen
+            .max_fps
+            .filter(|max_fps| *max_fps > 0.0)
+            .and_then(|max_fps| Duration::try_from_secs_f32(max_fps.recip()).ok());
+        let too_early = match (capture.last_capture, interval) {
+            (Some(last), Some(interval)) => now - last < interval,
+            _ => false,
+        };
+        if too_early || (offscreen.only_when_dirty && !capture.dirty) {
+            continue;
+        }
+        let Some(webview) = webviews.get(entity) else {
+            continue;
+        };
+        capture.dirty = false;
+        capture.last_capture = Some(now);
+        capture.pending = start_capture(webview).map(|slot| (slot, now));
+    }
+}
+
+fn update_image(images: &mut Assets<Image>, handle: &Handle<Image>, frame: Frame) {
+    let size = Extent3d {
+        width: frame.width,
+        height: frame.height,
+        depth_or_array_layers: 1,
+    };
+    match images.get_mut(handle) {
+        Some(image) if image.texture_descriptor.size == size => {
+            image.data = frame.data;
+        }
+        _ => {
+            let format = TextureFormat::Rgba8UnormSrgb;
+            let image = Image::new(size, TextureDimension::D2, frame.data, format);
+            images.set_untracked(handle, image);
+        }
+    }
+}
+
+/// Ask the webview engine for a capture of `webview`, returns where to find it once done.
+#[cfg(target_os = "linux")]
+fn start_capture(webview: &WebView) -> Option<CaptureSlot> {
+    use gtk::{cairo, gio};
+    use webkit2gtk::{SnapshotOptions, SnapshotRegion, WebViewExt};
+    use wry::webview::WebviewExtUnix;
+
+    let slot = Arc::new(Mutex::new(None));
+    let callback_slot = slot.clone();
+    let region = SnapshotRegion::Visible;
+    let options = SnapshotOptions::TRANSPARENT_BACKGROUND;
+    webview
+        .webview()
+        .snapshot(region, options, None::<&gio::Cancellable>, move |result| {
+            let surface = result.map_err(|err| err.to_string()).and_then(|surface| {
+                cairo::ImageSurface::try_from(surface).map_err(|_| "not an image".to_string())
+            });
+            let frame = surface.map(|surface| convert_surface(&surface));
+            *callback_slot.lock().unwrap() = Some(frame);
+        });
+    Some(slot)
+}
+#[cfg(not(target_os = "linux"))]
+fn start_capture(_: &WebView) -> Option<CaptureSlot> {
+    None
+}
+
+/// Convert a cairo surface, in premultiplied native endian ARGB, to RGBA.
+#[cfg(target_os = "linux")]
+fn convert_surface(surface: &gtk::cairo::ImageSurface) -> Frame {
+    surface.flush();
+    let (width, height) = (surface.width() as usize, surface.height() as usize);
+    let stride = surface.stride() as usize;
+    let mut data = Vec::with_capacity(width * height * 4);
+    surface
+        .with_data(|bytes| {
+            for row in bytes.chunks(stride).take(height) {
+                for pixel in row[..width * 4].chunks_exact(4) {
+                    let argb = u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                    let [a, r, g, b] = argb.to_be_bytes();
+                    let unpremultiply = |c: u8| match a {
+                        0 => 0,
+                        a => (u32::from(c) * 255 / u32::from(a)) as u8,
+                    };
+                    data.extend([unpremultiply(r), unpremultiply(g), unpremultiply(b), a]);
+                }
+            }
+        })
+        .unwrap();
+    Frame { width: width as u32, height: height as u32, data }
+}
+
+/// Queue the input for the pages, as calls to `window.__bevy_offscreen`.
+fn send_input(mut inputs: EventReader<OffscreenInput>, mut outbox: ResMut<Outbox>) {
+    for OffscreenInput { webview, input } in inputs.iter() {
+        let pressed = |state: &ButtonState| *state == ButtonState::Pressed;
+        let (function, args) = match input {
+            OffscreenInputKind::Move { uv } => ("move", json!([uv.x, uv.y])),
+            OffscreenInputKind::Button { uv, button, state } => {
+                let button = match button {
+                    MouseButton::Left => 0,
+                    MouseButton::Middle => 1,
+                    MouseButton::Right => 2,
+                    MouseButton::Other(other) => *other,
+                };
+                ("button", json!([uv.x, uv.y, button, pressed(state)]))
+            }
+            OffscreenInputKind::Wheel { uv, delta } => {
+                ("wheel", json!([uv.x, uv.y, delta.x, delta.y]))
+            }
+            OffscreenInputKind::Key { key_code, state } => {
+                let Some(code) = convert_key_code(*key_code) else {
+                    continue;
+                };
+                ("key", json!([code, pressed(state)]))
+            }
+            OffscreenInputKind::Text(text) => ("text", json!([text])),
+        };
+        outbox.send_script(*webview, offscreen_call(function, &args));
+    }
+}
+
+/// The script calling `function` of `window.__bevy_offscreen` with the array `args`.
+///
+/// Arguments go through JSON, which has no `NaN` nor infinity, they become `null`.
+fn offscreen_call(function: &str, args: &Value) -> String {
+    format!("window.__bevy_offscreen?.{function}(...{args})")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::offscreen_call;
+
+    #[test]
+    fn calls_are_valid_js() {
+        let call = offscreen_call("move", &json!([0.25f32, 0.5f32]));
+        assert_eq!(call, "window.__bevy_offscreen?.move(...[0.25,0.5])");
+        let call = offscreen_call("wheel", &json!([f32::NAN, 0.5f32, f32::INFINITY, 1.0f32]));
+        assert_eq!(
+            call,
+            "window.__bevy_offscreen?.wheel(...[null,0.5,null,1.0])"
+        );
+        let call = offscreen_call("text", &json!(["\"quoted\"\n"]));
+        assert_eq!(
+            call,
+            r#"window.__bevy_offscreen?.text(...["\"quoted\"\n"])"#
+        );
+    }
+}
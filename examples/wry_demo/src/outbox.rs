@@ -0,0 +1,260 @@
+//! Messages from bevy to webviews, sent in one batch per webview and frame.
+//!
+//! Each `evaluate_script` call is a round trip to the webview engine, so messages
+//! are queued in the [`Outbox`] and sent together at the end of the frame, by the
+//! systems of [`FlushOutbox`]. Messages with a key replace the queued message of
+//! the same key, so that fast-changing state is only sent once per frame.
+//!
+//! With [`Outbox::byte_budget`], messages that don't fit in a frame's batch wait
+//! for the next frames, in order. [`OUTBOX_QUEUE_DEPTH`] and [`OUTBOX_BATCH_SIZE`]
+//! measure how much is waiting and how much is sent.
+use std::collections::VecDeque;
+
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::web_errors::WebViewError;
+use crate::webview::WryWebviews;
+
+/// Number of messages still queued after a flush, over all webviews.
+pub const OUTBOX_QUEUE_DEPTH: DiagnosticId =
+    DiagnosticId::from_u128(0x2f0c_8e5a_61d4_4b7e_9a35_c1b0_7e42_d916);
+
+/// Bytes of script sent in a frame, over all webviews.
+pub const OUTBOX_BATCH_SIZE: DiagnosticId =
+    DiagnosticId::from_u128(0x8b17_3f6e_d2a9_4c05_b4e1_0f9d_63ac_5718);
+
+/// Systems sending the queued messages. Systems queuing messages in `Last` should
+/// run before it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FlushOutbox;
+
+/// Data pushed to the `bevy.on(CHANNEL, ...)` listeners of webviews.
+pub trait PushChannel: Serialize {
+    const CHANNEL: &'static str;
+}
+
+struct Message {
+    /// Messages with the same key replace each other, see [`Outbox::push_latest`].
+    key: Option<String>,
+    script: String,
+}
+
+/// Messages waiting to be sent to each webview.
+#[derive(Resource, Default)]
+pub struct Outbox {
+    queues: HashMap<Entity, VecDeque<Message>>,
+    /// Maximum bytes of script sent to a webview per frame, `None` for no limit.
+    ///
+    /// A message larger than the budget is still sent, alone in its batch.
+    pub byte_budget: Option<usize>,
+}
+impl Outbox {
+    /// Run `script` in `webview`.
+    pub fn send_script(&mut self, webview: Entity, script: String) {
+        self.enqueue(webview, None, script);
+    }
+    /// Answer the `bevy.call` of `id` made from `webview`.
+    pub fn reply(&mut self, webview: Entity, id: u64, result: Result<Value, String>) {
+        let message = match result {
+            Ok(value) => json!({ "type": "Reply", "id": id, "value": value }),
+            Err(error) => json!({ "type": "Reply", "id": id, "error": error }),
+        };
+        self.enqueue(webview, None, receive(&message));
+    }
+    /// Send `data` to the `bevy.on(T::CHANNEL, ...)` listeners of `webview`.
+    pub fn push<T: PushChannel>(&mut self, webview: Entity, data: &T) {
+        self.enqueue(webview, None, receive(&push_message(data)));
+    }
+    /// Like [`Outbox::push`], but replaces the queued message pushed with the same `key`.
+    ///
+    /// Only use this for data that supersedes the previous one, such as a full state.
+    pub fn push_latest<T: PushChannel>(&mut self, webview: Entity, key: &str, data: &T) {
+        let key = format!("{}/{key}", T::CHANNEL);
+        self.enqueue(webview, Some(key), receive(&push_message(data)));
+    }
+    /// Number of messages waiting to be sent, over all webviews.
+    pub fn queue_depth(&self) -> usize {
+        self.queues.values().map(VecDeque::len).sum()
+    }
+
+    fn enqueue(&mut self, webview: Entity, key: Option<String>, script: String) {
+        let queue = self.queues.entry(webview).or_default();
+        let queued = key
+            .as_ref()
+            .and_then(|key| queue.iter_mut().find(|m| m.key.as_ref() == Some(key)));
+        match queued {
+            Some(queued) => queued.script = script,
+            None => queue.push_back(Message { key, script }),
+        }
+    }
+}
+
+fn push_message<T: PushChannel>(data: &T) -> Value {
+    json!({ "type": "Push", "channel": T::CHANNEL, "data": data })
+}
+
+/// The script delivering `message` to `window.bevy`, see [`crate::bridge::BRIDGE_SCRIPT`].
+fn receive(message: &Value) -> String {
+    format!("window.bevy?.__receive({message})")
+}
+
+pub struct OutboxPlugin;
+impl Plugin for OutboxPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Outbox>()
+            .register_diagnostic(Diagnostic::new(
+                OUTBOX_QUEUE_DEPTH,
+                "outbox_queue_depth",
+                20,
+            ))
+            .register_diagnostic(
+                Diagnostic::new(OUTBOX_BATCH_SIZE, "outbox_batch_size", 20).with_suffix("B"),
+            )
+            .add_systems(Last, flush_outbox.in_set(FlushOutbox));
+    }
+}
+
+fn flush_outbox(
+    mut outbox: ResMut<Outbox>,
+    webviews: NonSend<WryWebviews>,
+    mut diagnostics: Diagnostics,
+    mut errors: EventWriter<WebViewError>,
+) {
+    let outbox = &mut *outbox;
+    outbox
+        .queues
+        .retain(|webview, _| webviews.get(*webview).is_some());
+
+    let mut sent = 0;
+    for (webview, queue) in &mut outbox.queues {
+        let batch = take_batch(queue, outbox.byte_budget);
+        if batch.is_empty() {
+            continue;
+        }
+        sent += batch.len();
+        if let Err(err) = webviews.get(*webview).unwrap().evaluate_script(&batch) {
+            errors.send(WebViewError::evaluate_script(*webview, err));
+        }
+    }
+    outbox.queues.retain(|_, queue| !queue.is_empty());
+
+    let depth = outbox.queue_depth();
+    diagnostics.add_measurement(OUTBOX_QUEUE_DEPTH, || depth as f64);
+    diagnostics.add_measurement(OUTBOX_BATCH_SIZE, || sent as f64);
+}
+
+/// The `try` block around each message of a batch.
+const TRY_START: &str = "try {\n";
+const TRY_END: &str = "\n} catch (error) { window.__bevy_report_error?.(error); }\n";
+
+/// Take the messages fitting in `byte_budget` from `queue`, as a single script.
+///
+/// Each message runs in its own `try` block, so that an error doesn't prevent the
+/// following messages from running. Errors are reported with
+/// [`crate::web_errors::ERRORS_SCRIPT`].
+fn take_batch(queue: &mut VecDeque<Message>, byte_budget: Option<usize>) -> String {
+    let mut batch = String::new();
+    while let Some(message) = queue.front() {
+        let len = TRY_START.len() + message.script.len() + TRY_END.len();
+        let over_budget = byte_budget.map_or(false, |budget| batch.len() + len > budget);
+        if over_budget && !batch.is_empty() {
+            break;
+        }
+        let message = queue.pop_front().unwrap();
+        batch.push_str(TRY_START);
+        batch.push_str(&message.script);
+        batch.push_str(TRY_END);
+    }
+    batch
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use serde::Serialize;
+
+    use super::{take_batch, Outbox, PushChannel};
+
+    #[derive(Serialize)]
+    struct Test(&'static str);
+    impl PushChannel for Test {
+        const CHANNEL: &'static str = "test";
+    }
+
+    fn script(name: &str) -> String {
+        format!("{name}({})", "0".repeat(80 - name.len() - 2))
+    }
+
+    #[test]
+    fn latest_replaces_in_place() {
+        let webview = Entity::from_raw(0);
+        let mut outbox = Outbox::default();
+        outbox.push_latest(webview, "Store", &Test("first"));
+        outbox.push(webview, &Test("log"));
+        outbox.push_latest(webview, "Store", &Test("second"));
+        outbox.push_latest(webview, "Other", &Test("other"));
+
+        let queue = &outbox.queues[&webview];
+        let scripts: Vec<&str> = queue.iter().map(|m| m.script.as_str()).collect();
+        assert_eq!(scripts.len(), 3);
+        assert!(scripts[0].contains("second") && !scripts[0].contains("first"));
+        assert!(scripts[1].contains("log"));
+        assert!(scripts[2].contains("other"));
+    }
+
+    #[test]
+    fn overflow_carries_to_next_batch() {
+        let webview = Entity::from_raw(0);
+        let mut outbox = Outbox::default();
+        for name in ["a", "b", "c"] {
+            outbox.send_script(webview, script(name));
+        }
+        let queue = outbox.queues.get_mut(&webview).unwrap();
+
+        // Two 80 bytes scripts and their `try` blocks fit in 300 bytes, not three.
+        let first = take_batch(queue, Some(300));
+        assert!(first.len() <= 300);
+        assert!(first.contains(&script("a")) && first.contains(&script("b")));
+        assert!(!first.contains(&script("c")));
+        assert_eq!(queue.len(), 1);
+
+        let second = take_batch(queue, Some(300));
+        assert!(second.contains(&script("c")));
+        assert!(queue.is_empty());
+        assert!(take_batch(queue, Some(300)).is_empty());
+    }
+
+    #[test]
+    fn oversized_message_is_sent_alone() {
+        let webview = Entity::from_raw(0);
+        let mut outbox = Outbox::default();
+        outbox.send_script(webview, script("big"));
+        outbox.send_script(webview, "small()".to_string());
+        let queue = outbox.queues.get_mut(&webview).unwrap();
+
+        let first = take_batch(queue, Some(50));
+        assert!(first.contains(&script("big")) && !first.contains("small()"));
+        let second = take_batch(queue, Some(50));
+        assert!(second.contains("small()"));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn unlimited_budget_sends_everything() {
+        let webview = Entity::from_raw(0);
+        let mut outbox = Outbox::default();
+        for name in ["a", "b", "c"] {
+            outbox.send_script(webview, script(name));
+        }
+        let queue = outbox.queues.get_mut(&webview).unwrap();
+        let batch = take_batch(queue, None);
+        assert!(["a", "b", "c"]
+            .iter()
+            .all(|name| batch.contains(&script(name))));
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+//! Click-through webview overlays.
+//!
+//! A webview with [`ClickThrough`] only catches the pointer over interactive
+//! elements of the page. Everywhere else, pointer events go through to the bevy
+//! window below, which gets them as the usual `CursorMoved` and `MouseButtonInput`.
+//!
+//! The page reports where its interactive elements are with [`CLICK_THROUGH_SCRIPT`].
+use bevy::prelude::*;
+use serde::Deserialize;
+use ts_rs::TS;
+
+use crate::webview::{WebViewZoom, WryWebviews};
+
+/// Initialization script reporting the interactive regions of the page.
+///
+/// An element is interactive if it is a link, a form control, has a click handler
+/// attribute, a button or link role, or the `data-bevy-interactive` attribute.
+pub const CLICK_THROUGH_SCRIPT: &str = r#"(() => {
+    const INTERACTIVE = "a[href], button, input, select, textarea, label, summary, "
+        + "[onclick], [contenteditable], [role=button], [role=link], [data-bevy-interactive]";
+    let scheduled = false;
+    let last_report = null;
+    function report() {
+        scheduled = false;
+        const regions = Array.from(document.querySelectorAll(INTERACTIVE))
+            .flatMap((element) => Array.from(element.getClientRects()))
+            .filter((rect) => rect.width > 0 && rect.height > 0
+                && rect.right > 0 && rect.bottom > 0
+                && rect.left < window.innerWidth && rect.top < window.innerHeight)
+            .map((rect) => ({ x: rect.left, y: rect.top, width: rect.width, height: rect.height }));
+        const message = JSON.stringify({ type: "InteractiveRegions", regions });
+        if (message !== last_report) {
+            last_report = message;
+            window.ipc.postMessage(message);
+        }
+    }
+    function schedule() {
+        if (!scheduled) {
+            scheduled = true;
+            requestAnimationFrame(report);
+        }
+    }
+    window.addEventListener("DOMContentLoaded", () => {
+        const observer = new MutationObserver(schedule);
+        observer.observe(document.documentElement, {
+            subtree: true, childList: true, attributes: true, characterData: true,
+        });
+        schedule();
+    });
+    window.addEventListener("load", schedule);
+    window.addEventListener("resize", schedule);
+    window.addEventListener("scroll", schedule, true);
+    window.addEventListener("transitionend", schedule, true);
+})();"#;
+
+/// Let pointer events through this webview, except over interactive elements.
+///
+/// The webview must be built with [`CLICK_THROUGH_SCRIPT`].
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct ClickThrough;
+
+/// Regions of the page with interactive elements, in CSS pixels relative to the viewport.
+///
+/// Updated by [`CLICK_THROUGH_SCRIPT`] whenever the page layout changes.
+#[derive(Component, Debug, Default, Clone)]
+pub struct InteractiveRegions(pub Vec<Rect>);
+
+/// A rectangle as reported by `Element.getClientRects()`.
+#[derive(Deserialize, Debug, Clone, Copy, TS)]
+pub struct Region {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+impl Region {
+    pub fn into_rect(self) -> Rect {
+        Rect::new(self.x, self.y, self.x + self.width, self.y + self.height)
+    }
+}
+
+pub struct OverlayPlugin;
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Last, (restore_hit_test, update_hit_test));
+    }
+}
+
+type ChangedOverlay = Or<(
+    Changed<InteractiveRegions>,
+    Changed<WebViewZoom>,
+    Added<ClickThrough>,
+)>;
+
+/// Only accept pointer input over the interactive regions of click-through webviews.
+///
+/// Until the page reports its regions, none of it is interactive.
+fn update_hit_test(
+    overlays: Query<
+        (Entity, Option<&InteractiveRegions>, Option<&WebViewZoom>),
+        (With<ClickThrough>, ChangedOverlay),
+    >,
+    webviews: NonSend<WryWebviews>,
+) {
+    for (entity, regions, zoom) in &overlays {
+        let Some(webview) = webviews.get(entity) else {
+            continue;
+        };
+        let regions = regions.map_or(&[][..], |regions| &regions.0);
+        let zoom = zoom.map_or(1.0, |zoom| zoom.0 as f32);
+        set_input_region(webview, Some((regions, zoom)));
+    }
+}
+
+/// Accept pointer input everywhere again on webviews that stopped being click-through.
+fn restore_hit_test(mut removed: RemovedComponents<ClickThrough>, webviews: NonSend<WryWebviews>) {
+    for entity in removed.iter() {
+        if let Some(webview) = webviews.get(entity) {
+            set_input_region(webview, None);
+        }
+    }
+}
+
+/// Restrict the pointer input of the webview window to `regions` scaled by the page zoom,
+/// or the whole window if `None`.
+#[cfg(target_os = "linux")]
+fn set_input_region(webview: &wry::webview::WebView, regions: Option<(&[Rect], f32)>) {
+    use gtk::cairo::{RectangleInt, Region};
+    use gtk::prelude::WidgetExt;
+    use wry::application::platform::unix::WindowExtUnix;
+
+    let region = regions.map(|(regions, zoom)| {
+        let region = Region::create();
+        for rect in regions {
+            let min = rect.min * zoom;
+            let max = rect.max * zoom;
+            let (x, y) = (min.x.floor() as i32, min.y.floor() as i32);
+            let (width, height) = (max.x.ceil() as i32 - x, max.y.ceil() as i32 - y);
+            if let Err(err) = region.union_rectangle(&RectangleInt::new(x, y, width, height)) {
+                warn!("Could not add {rect:?} to the webview input region: {err}");
+            }
+        }
+        region
+    });
+    webview
+        .window()
+        .gtk_window()
+        .input_shape_combine_region(region.as_ref());
+}
+#[cfg(not(target_os = "linux"))]
+fn set_input_region(_: &wry::webview::WebView, regions: Option<(&[Rect], f32)>) {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    if regions.is_some() {
+        WARNED.call_once(|| warn!("Click-through webviews are only supported on linux"));
+    }
+}
@@ -0,0 +1,219 @@
+//! TypeScript definitions of the bridge, generated from the Rust types.
+//!
+//! The definitions are checked in at [`BINDINGS_PATH`], for the web UI to import.
+//! The demo writes them when run with `--write-bindings`, and a test fails when they
+//! are stale. Regenerate them from the workspace root with:
+//!
+//! ```sh
+//! make bindings
+//! ```
+use std::fmt::Write;
+use std::path::Path;
+use std::{fs, io};
+
+use ts_rs::TS;
+
+use crate::bridge::{Event, Request};
+use crate::dom_watch::DomMatch;
+use crate::forward_input::{DomButtonState, DomInput};
+use crate::inspector::{InspectorDiff, InspectorEntity, InspectorOp};
+use crate::outbox::PushChannel;
+use crate::overlay::Region;
+use crate::web_commands::WebCommand;
+use crate::web_log::{ConsoleLevel, WebLogRecord};
+use crate::web_sync::{SyncPatch, SyncPush};
+
+/// Where the generated definitions are, relative to the crate root.
+pub const BINDINGS_PATH: &str = "ui/bridge.d.ts";
+
+/// A `bevy.call` request answered with a different type for each variant.
+pub trait CallReplies {
+    /// The field naming the variant, the serde tag of the enum.
+    const TAG: &'static str;
+    /// The TypeScript type of the answer to each variant.
+    fn replies() -> Vec<(&'static str, String)>;
+}
+
+/// The typed client of `window.bevy`, as defined by the bridge initialization scripts.
+const CLIENT: &str = r#"/** `bevy.inspector`, see `inspector::INSPECTOR_SCRIPT`. */
+export interface Inspector {
+  entities(): Promise<CallResult<"Inspector", { op: "Entities" }>>;
+  components(entity: number): Promise<CallResult<"Inspector", { op: "Components" }>>;
+  set(entity: number, component: string, value: unknown): Promise<CallResult<"Inspector", { op: "Set" }>>;
+  /** The callback is first called with all the components of the entity. Resolves to a function unwatching. */
+  watch(entity: number, callback: (diff: InspectorDiff) => void): Promise<() => void>;
+}
+
+/** `bevy.commands`, see `web_commands::COMMANDS_SCRIPT`. */
+export interface Commands {
+  spawn(components?: Record<string, unknown>): Promise<CallResult<"Command", { command: "Spawn" }>>;
+  insert(entity: number, components: Record<string, unknown>): Promise<CallResult<"Command", { command: "Insert" }>>;
+  remove(entity: number, components: Array<string>): Promise<CallResult<"Command", { command: "Remove" }>>;
+  despawn(entity: number): Promise<CallResult<"Command", { command: "Despawn" }>>;
+  sendEvent(event: string, value: unknown): Promise<CallResult<"Command", { command: "SendEvent" }>>;
+}
+
+/** `bevy.buffers`, see `binary::BUFFERS_SCRIPT`. */
+export interface Buffers {
+  url(id: string): string;
+  fetch(id: string): Promise<ArrayBuffer>;
+  upload(id: string, bytes: BodyInit): Promise<void>;
+  release(id: string): Promise<void>;
+}
+
+/** The `window.bevy` object, see `bridge::BRIDGE_SCRIPT`. */
+export interface Bevy {
+  /** Post a request without answer. */
+  send(request: Exclude<Request, { id: number }>): void;
+  /** Post a request, and get its answer. */
+  call<T extends CallType, A extends CallArgs<T>>(type: T, args: A): Promise<CallResult<T, A>>;
+  /** Listen to the data pushed on `channel`, returns a function removing the listener. */
+  on<C extends keyof Channels>(channel: C, callback: (data: Channels[C]) => void): () => void;
+  /** Get the value of a synced store, and its changes. Returns a function unsubscribing. */
+  subscribe(store: string, callback: (value: unknown) => void): () => void;
+  inspector: Inspector;
+  commands: Commands;
+  buffers: Buffers;
+}
+
+declare global {
+  interface Window {
+    bevy: Bevy;
+  }
+  const bevy: Bevy;
+}
+"#;
+
+/// The arguments of `bevy.call`, derived from [`Request`].
+const CALL_ARGS: &str = r#"type DistributiveOmit<T, K extends PropertyKey> = T extends unknown ? Omit<T, K> : never;
+
+/** The requests answered by `bevy.call`. */
+export type CallType = Extract<Request, { id: number }>["type"];
+
+/** The arguments of the `bevy.call` of `T`. */
+export type CallArgs<T extends CallType> = DistributiveOmit<Extract<Request, { type: T }>, "type" | "id">;
+"#;
+
+/// The content of [`BINDINGS_PATH`].
+pub fn bindings() -> String {
+    let declarations = [
+        Request::decl(),
+        Event::decl(),
+        Region::decl(),
+        DomInput::decl(),
+        DomButtonState::decl(),
+        InspectorOp::decl(),
+        InspectorEntity::decl(),
+        InspectorDiff::decl(),
+        WebCommand::decl(),
+        ConsoleLevel::decl(),
+        DomMatch::decl(),
+        WebLogRecord::decl(),
+        SyncPush::decl(),
+        SyncPatch::decl(),
+    ];
+    let mut bindings = String::from(
+        "// Generated from the Rust bridge types by `wry_demo::typescript`, do not edit.\n\
+         // Regenerate with `make bindings`.\n\n",
+    );
+    for declaration in declarations {
+        bindings.push_str("export ");
+        bindings.push_str(&declaration);
+        bindings.push_str("\n\n");
+    }
+    bindings.push_str(CALL_ARGS);
+    bindings.push('\n');
+    bindings.push_str(&call_result());
+    bindings.push('\n');
+    bindings.push_str(&channels());
+    bindings.push('\n');
+    bindings.push_str(CLIENT);
+    bindings
+}
+
+/// Write [`bindings`] to [`BINDINGS_PATH`].
+pub fn write_bindings() -> io::Result<()> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(BINDINGS_PATH);
+    fs::write(path, bindings())
+}
+
+/// `CallResult`, the answers to each call of [`Request`].
+fn call_result() -> String {
+    let mut ts = String::from(
+        "/** The answer to the `bevy.call` of `T` with the arguments `A`. */\n\
+         export type CallResult<T extends CallType, A> =\n",
+    );
+    let mut call = |kind: &str, tag: &str, replies: Vec<(&str, String)>| {
+        writeln!(ts, "  T extends {kind:?} ? (").unwrap();
+        for (variant, reply) in replies {
+            writeln!(ts, "    A extends {{ {tag}: {variant:?} }} ? {reply} :").unwrap();
+        }
+        ts.push_str("    unknown\n  ) :\n");
+    };
+    call("Inspector", InspectorOp::TAG, InspectorOp::replies());
+    call("Command", WebCommand::TAG, WebCommand::replies());
+    // Subscribe is answered with the value of the store.
+    ts.push_str("  unknown;\n");
+    ts
+}
+
+/// `Channels`, the data pushed on each channel.
+fn channels() -> String {
+    let channels = [
+        (InspectorDiff::CHANNEL, InspectorDiff::name()),
+        (SyncPush::CHANNEL, SyncPush::name()),
+        (WebLogRecord::CHANNEL, WebLogRecord::name()),
+    ];
+    let mut ts = String::from(
+        "/** The data pushed on each channel, see `Outbox::push`. */\n\
+         export interface Channels {\n",
+    );
+    for (channel, data) in channels {
+        writeln!(ts, "  {channel}: {data};").unwrap();
+    }
+    ts.push_str("}\n");
+    ts
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use ts_rs::TS;
+
+    use super::{bindings, CallReplies, BINDINGS_PATH};
+    use crate::inspector::InspectorOp;
+    use crate::web_commands::WebCommand;
+
+    #[test]
+    fn bindings_are_up_to_date() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(BINDINGS_PATH);
+        let checked_in = fs::read_to_string(path).unwrap_or_default();
+        assert!(
+            checked_in == bindings(),
+            "{BINDINGS_PATH} is stale, regenerate it with `make bindings`"
+        );
+    }
+
+    /// The variants of `T`, from its TypeScript declaration.
+    fn variants<T: TS + CallReplies>() -> Vec<String> {
+        let tag = format!("\"{}\": \"", T::TAG);
+        let decl = T::decl();
+        let variants = decl.split(&tag).skip(1);
+        variants
+            .map(|rest| rest.split('"').next().unwrap().to_string())
+            .collect()
+    }
+
+    fn replied<T: CallReplies>() -> Vec<String> {
+        let replies = T::replies().into_iter();
+        replies.map(|(variant, _)| variant.to_string()).collect()
+    }
+
+    #[test]
+    fn every_call_has_a_reply_type() {
+        assert_eq!(variants::<InspectorOp>(), replied::<InspectorOp>());
+        assert_eq!(variants::<WebCommand>(), replied::<WebCommand>());
+    }
+}
@@ -0,0 +1,171 @@
+//! Serve the bundled web UI to webviews, through the `ui://` custom protocol.
+//!
+//! The UI lives in the `ui` directory of this crate. Release builds embed it
+//! in the binary, so that the game works offline and never loads remote code.
+//! Debug builds read it from disk instead, and reload webviews showing it when
+//! a file changes.
+//!
+//! Paths without a file extension that do not exist fall back to `index.html`,
+//! so that single page apps can route with the history API.
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use bevy::prelude::*;
+#[cfg(not(debug_assertions))]
+use include_dir::include_dir;
+use include_dir::Dir;
+use wry::http::{Request, Response};
+
+use crate::asset_protocol::{mime_type, not_found, request_path, serve};
+use crate::web_errors::WebViewError;
+use crate::webview::WryWebviews;
+
+/// The name of the custom protocol.
+pub const SCHEME: &str = "ui";
+
+/// The origin of the UI pages.
+pub const ORIGIN: &str = "ui://app";
+
+/// The url of the UI entry point.
+pub const INDEX_URL: &str = "ui://app/";
+
+const HOST: &str = "app";
+const INDEX: &str = "index.html";
+
+/// How often to check for changes to the UI directory in dev mode.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+#[cfg(not(debug_assertions))]
+static EMBEDDED_UI: Dir = include_dir!("$CARGO_MANIFEST_DIR/ui");
+
+/// Where the UI files come from.
+#[derive(Debug, Clone)]
+pub enum UiSource {
+    /// Files embedded in the binary.
+    Embedded(&'static Dir<'static>),
+    /// Files read from a local directory, reloading webviews when they change.
+    Directory(PathBuf),
+}
+impl Default for UiSource {
+    /// Embedded files in release builds, the crate's `ui` directory in debug builds.
+    fn default() -> Self {
+        #[cfg(not(debug_assertions))]
+        return UiSource::Embedded(&EMBEDDED_UI);
+        #[cfg(debug_assertions)]
+        return UiSource::Directory(Path::new(env!("CARGO_MANIFEST_DIR")).join("ui"));
+    }
+}
+impl UiSource {
+    fn read(&self, path: &Path) -> Option<Cow<'static, [u8]>> {
+        match self {
+            UiSource::Embedded(dir) => dir.get_file(path).map(|f| Cow::Borrowed(f.contents())),
+            UiSource::Directory(root) => fs::read(root.join(path)).ok().map(Cow::Owned),
+        }
+    }
+}
+
+/// The source of the web UI, set it before the webviews are created.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct UiBundle(pub Arc<UiSource>);
+
+pub struct UiBundlePlugin;
+impl Plugin for UiBundlePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<UiBundle>()
+            .init_resource::<UiWatcher>()
+            .add_systems(Last, reload_on_change);
+    }
+}
+
+/// The `ui://` protocol handler, to pass to `WebViewBuilder::with_custom_protocol`.
+pub fn handler(
+    world: &World,
+) -> impl Fn(&Request<Vec<u8>>) -> wry::Result<Response<Cow<'static, [u8]>>> + 'static {
+    let source = world.resource::<UiBundle>().0.clone();
+    move |request| {
+        let path = request_path(request.uri());
+        let Some(path) = path.as_deref().and_then(|p| p.strip_prefix(HOST).ok()) else {
+            return Ok(not_found());
+        };
+        let path = if path.as_os_str().is_empty() { Path::new(INDEX) } else { path };
+
+        let file = match source.read(path) {
+            Some(bytes) => Some((path, bytes)),
+            // Single page app fallback
+            None if path.extension().is_none() => {
+                let index = Path::new(INDEX);
+                source.read(index).map(|bytes| (index, bytes))
+            }
+            None => None,
+        };
+        Ok(match file {
+            Some((path, bytes)) => serve(request, mime_type(path), &bytes),
+            None => not_found(),
+        })
+    }
+}
+
+/// Last time the UI directory was checked for changes, and the latest modification seen.
+#[derive(Resource)]
+struct UiWatcher {
+    timer: Timer,
+    last_modified: Option<SystemTime>,
+}
+impl Default for UiWatcher {
+    fn default() -> Self {
+        UiWatcher {
+            timer: Timer::new(WATCH_INTERVAL, TimerMode::Repeating),
+            last_modified: None,
+        }
+    }
+}
+
+/// Reload the webviews showing the UI when a file of the UI directory changes.
+fn reload_on_change(
+    bundle: Res<UiBundle>,
+    mut watcher: ResMut<UiWatcher>,
+    time: Res<Time>,
+    webviews: NonSend<WryWebviews>,
+    mut errors: EventWriter<WebViewError>,
+) {
+    let UiSource::Directory(root) = bundle.0.as_ref() else {
+        return;
+    };
+    if !watcher.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let last_modified = latest_modification(root);
+    let changed = watcher.last_modified.is_some() && watcher.last_modified != last_modified;
+    watcher.last_modified = last_modified;
+    if !changed {
+        return;
+    }
+    info!("UI files changed, reloading webviews");
+    for (entity, webview) in webviews.iter() {
+        if webview.url().scheme() != SCHEME {
+            continue;
+        }
+        if let Err(err) = webview.evaluate_script("window.location.reload()") {
+            errors.send(WebViewError::evaluate_script(entity, err));
+        }
+    }
+}
+
+/// The latest modification time of the files in `dir`, recursively.
+fn latest_modification(dir: &Path) -> Option<SystemTime> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            if metadata.is_dir() {
+                latest_modification(&entry.path())
+            } else {
+                metadata.modified().ok()
+            }
+        })
+        .max()
+}
@@ -0,0 +1,502 @@
+//! Spawn, despawn and mutate entities, and send events, from javascript.
+//!
+//! Components and events are given by type name, or short type name, with their value
+//! as JSON, and go through `bevy_reflect`. They must be registered in the
+//! `AppTypeRegistry`, components with `#[reflect(Component)]`, events with
+//! [`WebCommandsAppExt::add_web_event`].
+//!
+//! A webview can only use the component and event types allowed by its
+//! [`WebCommandPermissions`], only insert and remove components on the entities it
+//! spawned or that its permissions allow, and only despawn the entities it spawned.
+//! Descendants of a despawned entity that the webview didn't spawn are kept.
+//!
+//! From JS, with the `bevy.commands` object defined by [`COMMANDS_SCRIPT`]:
+//!
+//! ```js
+//! const entity = await bevy.commands.spawn({ Transform: { translation: [0, 0, 0], ... } });
+//! await bevy.commands.insert(entity, { Visibility: "Hidden" });
+//! await bevy.commands.remove(entity, ["Visibility"]);
+//! await bevy.commands.despawn(entity);
+//! await bevy.commands.sendEvent("NewPage", { links: ["https://bevyengine.org"] });
+//! ```
+//!
+//! Entities are identified by their `Entity::to_bits` value.
+use std::any::TypeId;
+
+use bevy::ecs::entity::Entities;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::reflect::serde::TypedReflectDeserializer;
+use bevy::reflect::{FromReflect, GetTypeRegistration, TypeRegistration, TypeRegistry};
+use bevy::utils::{HashMap, HashSet};
+use serde::de::DeserializeSeed;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use ts_rs::TS;
+
+use crate::outbox::Outbox;
+use crate::typescript::CallReplies;
+
+/// Initialization script defining `bevy.commands`, it must run after
+/// [`crate::bridge::BRIDGE_SCRIPT`].
+pub const COMMANDS_SCRIPT: &str = r#"(() => {
+    const call = (command, args = {}) => bevy.call("Command", { ...args, command });
+    bevy.commands = {
+        spawn: (components = {}) => call("Spawn", { components }),
+        insert: (entity, components) => call("Insert", { entity, components }),
+        remove: (entity, components) => call("Remove", { entity, components }),
+        despawn: (entity) => call("Despawn", { entity }),
+        sendEvent: (event, value) => call("SendEvent", { event, value }),
+    };
+})();"#;
+
+/// Commands sent from javascript, see the [module documentation](self).
+#[derive(Deserialize, Debug, TS)]
+#[serde(tag = "command")]
+pub enum WebCommand {
+    /// Spawn an entity with `components`, answered with the new entity.
+    Spawn {
+        #[ts(type = "Record<string, unknown>")]
+        components: Map<String, Value>,
+    },
+    Insert {
+        #[ts(type = "number")]
+        entity: u64,
+        #[ts(type = "Record<string, unknown>")]
+        components: Map<String, Value>,
+    },
+    Remove {
+        #[ts(type = "number")]
+        entity: u64,
+        components: Vec<String>,
+    },
+    /// Despawn an entity spawned by the same webview, with its descendants if the
+    /// webview spawned them all. Otherwise, its children are detached and kept.
+    Despawn {
+        #[ts(type = "number")]
+        entity: u64,
+    },
+    SendEvent {
+        event: String,
+        #[ts(type = "unknown")]
+        value: Value,
+    },
+}
+impl CallReplies for WebCommand {
+    const TAG: &'static str = "command";
+    fn replies() -> Vec<(&'static str, String)> {
+        let replies = [
+            ("Spawn", "number"),
+            ("Insert", "null"),
+            ("Remove", "null"),
+            ("Despawn", "null"),
+            ("SendEvent", "null"),
+        ];
+        replies
+            .map(|(command, reply)| (command, reply.to_string()))
+            .into()
+    }
+}
+
+/// The component and event types the webview of this entity can use in [`WebCommand`]s.
+///
+/// Webviews without it can't use commands.
+#[derive(Component, Debug, Default, Clone)]
+pub struct WebCommandPermissions {
+    components: HashSet<TypeId>,
+    events: HashSet<TypeId>,
+    entities: HashSet<Entity>,
+}
+impl WebCommandPermissions {
+    /// Allow spawning entities with `C`, and inserting or removing `C` on the entities
+    /// the webview can modify.
+    pub fn allow_component<C: Component>(mut self) -> Self {
+        self.components.insert(TypeId::of::<C>());
+        self
+    }
+    /// Allow inserting and removing the allowed components on `entity`, on top of the
+    /// entities spawned by the webview.
+    pub fn allow_entity(mut self, entity: Entity) -> Self {
+        self.entities.insert(entity);
+        self
+    }
+    /// Allow sending `E`, it must be registered with [`WebCommandsAppExt::add_web_event`].
+    pub fn allow_event<E: Event>(mut self) -> Self {
+        self.events.insert(TypeId::of::<E>());
+        self
+    }
+}
+
+/// An entity spawned by the webview of entity `0`.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct WebSpawned(pub Entity);
+
+/// Commands received from webviews, with the webview and call id.
+#[derive(Resource, Default)]
+struct WebCommandQueue(Vec<(Entity, u64, WebCommand)>);
+
+/// How to send each event type that webviews can send, by type.
+#[derive(Resource, Default)]
+struct WebEvents(HashMap<TypeId, fn(&mut World, &dyn Reflect)>);
+
+pub struct WebCommandsPlugin;
+impl Plugin for WebCommandsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WebCommandQueue>()
+            .init_resource::<WebEvents>()
+            .add_systems(PreUpdate, apply_web_commands);
+    }
+}
+
+/// Register events that webviews can send.
+pub trait WebCommandsAppExt {
+    /// Add the event `E`, and let webviews send it with [`WebCommand::SendEvent`].
+    fn add_web_event<E: Event + FromReflect + GetTypeRegistration>(&mut self) -> &mut Self;
+}
+impl WebCommandsAppExt for App {
+    fn add_web_event<E: Event + FromReflect + GetTypeRegistration>(&mut self) -> &mut Self {
+        self.init_resource::<WebEvents>();
+        let mut web_events = self.world.resource_mut::<WebEvents>();
+        web_events.0.insert(TypeId::of::<E>(), send_event::<E>);
+        self.add_event::<E>().register_type::<E>()
+    }
+}
+
+fn send_event<E: Event + FromReflect>(world: &mut World, value: &dyn Reflect) {
+    match E::from_reflect(value) {
+        Some(event) => world.send_event(event),
+        None => warn!("Invalid {} sent from a webview", value.type_name()),
+    }
+}
+
+/// Queue the command `id` from `webview`, it is applied by [`apply_web_commands`].
+pub fn queue(world: &mut World, webview: Entity, id: u64, command: WebCommand) {
+    let mut queue = world.resource_mut::<WebCommandQueue>();
+    queue.0.push((webview, id, command));
+}
+
+/// Apply the commands received from webviews, and answer them.
+fn apply_web_commands(
+    mut commands: Commands,
+    mut queue: ResMut<WebCommandQueue>,
+    registry: Res<AppTypeRegistry>,
+    web_events: Res<WebEvents>,
+    permissions: Query<&WebCommandPermissions>,
+    web_entities: WebEntities,
+    mut outbox: ResMut<Outbox>,
+) {
+    let registry = registry.read();
+    for (webview, id, command) in queue.0.drain(..) {
+        let result = match permissions.get(webview) {
+            Ok(permissions) => {
+                let context = Context {
+                    webview,
+                    permissions,
+                    registry: &registry,
+                    web_events: &web_events,
+                    entities: web_entities.entities,
+                };
+                context.apply(&mut commands, command, &web_entities)
+            }
+            Err(_) => Err("This webview can't use commands".to_string()),
+        };
+        outbox.reply(webview, id, result);
+    }
+}
+
+/// The entities commands can refer to, and which webview spawned them.
+#[derive(SystemParam)]
+struct WebEntities<'w, 's> {
+    entities: &'w Entities,
+    spawned: Query<'w, 's, &'static WebSpawned>,
+    children: Query<'w, 's, &'static Children>,
+}
+
+/// What a command needs to be checked and applied.
+struct Context<'a> {
+    webview: Entity,
+    permissions: &'a WebCommandPermissions,
+    registry: &'a TypeRegistry,
+    web_events: &'a WebEvents,
+    entities: &'a Entities,
+}
+impl Context<'_> {
+    fn apply(
+        &self,
+        commands: &mut Commands,
+        command: WebCommand,
+        web_entities: &WebEntities,
+    ) -> Result<Value, String> {
+        let spawned = &web_entities.spawned;
+        match command {
+            WebCommand::Spawn { components } => {
+                let components = self.components(components)?;
+                let entity = commands.spawn(WebSpawned(self.webview)).id();
+                insert(commands, entity, components);
+                Ok(Value::from(entity.to_bits()))
+            }
+            WebCommand::Insert { entity, components } => {
+                let entity = self.modifiable_entity(entity, spawned)?;
+                let components = self.components(components)?;
+                insert(commands, entity, components);
+                Ok(Value::Null)
+            }
+            WebCommand::Remove { entity, components } => {
+                let entity = self.modifiable_entity(entity, spawned)?;
+                let components = components
+                    .iter()
+                    .map(|name| self.component_registration(name))
+                    .collect::<Result<Vec<_>, _>>()?;
+                for (_, reflect_component) in components {
+                    commands
+                        .entity(entity)
+                        .add(move |entity, world: &mut World| {
+                            if let Some(mut entity) = world.get_entity_mut(entity) {
+                                reflect_component.remove(&mut entity);
+                            }
+                        });
+                }
+                Ok(Value::Null)
+            }
+            WebCommand::Despawn { entity } => {
+                let entity = self.entity(entity)?;
+                if !self.spawned_here(entity, spawned) {
+                    return Err(format!("{entity:?} wasn't spawned by this webview"));
+                }
+                let mut descendants = web_entities.children.iter_descendants(entity);
+                if descendants.all(|descendant| self.spawned_here(descendant, spawned)) {
+                    commands.entity(entity).despawn_recursive();
+                } else {
+                    // Other entities may have been parented to it, they aren't the webview's.
+                    let mut entity = commands.entity(entity);
+                    entity.remove_parent().clear_children().despawn();
+                }
+                Ok(Value::Null)
+            }
+            WebCommand::SendEvent { event, value } => {
+                let registration = self.registration(&event)?;
+                let type_id = registration.type_id();
+                if !self.permissions.events.contains(&type_id) {
+                    return Err(format!("This webview can't send {event}"));
+                }
+                let Some(&send) = self.web_events.0.get(&type_id) else {
+                    return Err(format!("{event} is not a web event"));
+                };
+                let value = deserialize(registration, self.registry, value)?;
+                commands.add(move |world: &mut World| send(world, &*value));
+                Ok(Value::Null)
+            }
+        }
+    }
+
+    fn entity(&self, bits: u64) -> Result<Entity, String> {
+        let entity = Entity::from_bits(bits);
+        match self.entities.contains(entity) {
+            true => Ok(entity),
+            false => Err(format!("There is no entity {entity:?}")),
+        }
+    }
+
+    fn spawned_here(&self, entity: Entity, spawned: &Query<&WebSpawned>) -> bool {
+        spawned.get(entity).ok().map(|s| s.0) == Some(self.webview)
+    }
+
+    /// An entity the webview can insert components on and remove components from.
+    fn modifiable_entity(&self, bits: u64, spawned: &Query<&WebSpawned>) -> Result<Entity, String> {
+        let entity = self.entity(bits)?;
+        let allowed = self.permissions.entities.contains(&entity);
+        match allowed || self.spawned_here(entity, spawned) {
+            true => Ok(entity),
+            false => Err(format!("This webview can't modify {entity:?}")),
+        }
+    }
+
+    fn registration(&self, type_name: &str) -> Result<&TypeRegistration, String> {
+        let registry = self.registry;
+        registry
+            .get_with_name(type_name)
+            .or_else(|| registry.get_with_short_name(type_name))
+            .ok_or_else(|| format!("Unknown type {type_name}"))
+    }
+
+    /// The registration of an allowed component type.
+    fn component_registration(
+        &self,
+        type_name: &str,
+    ) -> Result<(&TypeRegistration, ReflectComponent), String> {
+        let registration = self.registration(type_name)?;
+        if !self
+            .permissions
+            .components
+            .contains(&registration.type_id())
+        {
+            return Err(format!("This webview can't use {type_name}"));
+        }
+        let reflect_component = registration
+            .data::<ReflectComponent>()
+            .ok_or_else(|| format!("{type_name} is not a reflected component"))?;
+        Ok((registration, reflect_component.clone()))
+    }
+
+    /// Deserialize allowed components.
+    fn components(
+        &self,
+        components: Map<String, Value>,
+    ) -> Result<Vec<(ReflectComponent, Box<dyn Reflect>)>, String> {
+        let components = components.into_iter().map(|(type_name, value)| {
+            let (registration, reflect_component) = self.component_registration(&type_name)?;
+            let component = deserialize(registration, self.registry, value)?;
+            Ok((reflect_component, component))
+        });
+        components.collect()
+    }
+}
+
+fn deserialize(
+    registration: &TypeRegistration,
+    registry: &TypeRegistry,
+    value: Value,
+) -> Result<Box<dyn Reflect>, String> {
+    TypedReflectDeserializer::new(registration, registry)
+        .deserialize(value)
+        .map_err(|err| format!("Invalid {}: {err}", registration.type_name()))
+}
+
+fn insert(
+    commands: &mut Commands,
+    entity: Entity,
+    components: Vec<(ReflectComponent, Box<dyn Reflect>)>,
+) {
+    commands
+        .entity(entity)
+        .add(move |entity, world: &mut World| {
+            let Some(mut entity) = world.get_entity_mut(entity) else {
+                return;
+            };
+            for (reflect_component, component) in &components {
+                reflect_component.insert(&mut entity, &**component);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Component, Reflect, Default)]
+    #[reflect(Component)]
+    struct Score {
+        value: u32,
+    }
+
+    #[derive(Component, Reflect, Default)]
+    #[reflect(Component)]
+    struct Secret {
+        value: u32,
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(WebCommandsPlugin)
+            .init_resource::<Outbox>()
+            .register_type::<Score>()
+            .register_type::<Secret>();
+        app
+    }
+
+    fn insert_score(app: &mut App, webview: Entity, entity: Entity) {
+        let components = json!({ "Score": { "value": 1 } });
+        let Value::Object(components) = components else {
+            unreachable!()
+        };
+        let entity = entity.to_bits();
+        queue(
+            &mut app.world,
+            webview,
+            0,
+            WebCommand::Insert { entity, components },
+        );
+        app.update();
+    }
+
+    #[test]
+    fn insert_only_on_own_or_allowed_entities() {
+        let mut app = app();
+        let other = app.world.spawn_empty().id();
+        let allowed = app.world.spawn_empty().id();
+        let permissions = WebCommandPermissions::default()
+            .allow_component::<Score>()
+            .allow_entity(allowed);
+        let webview = app.world.spawn(permissions).id();
+        let own = app.world.spawn(WebSpawned(webview)).id();
+        let foreign = app.world.spawn(WebSpawned(other)).id();
+
+        for entity in [own, allowed, foreign, other, webview] {
+            insert_score(&mut app, webview, entity);
+        }
+        assert!(app.world.get::<Score>(own).is_some());
+        assert!(app.world.get::<Score>(allowed).is_some());
+        assert!(app.world.get::<Score>(foreign).is_none());
+        assert!(app.world.get::<Score>(other).is_none());
+        assert!(app.world.get::<Score>(webview).is_none());
+    }
+
+    #[test]
+    fn remove_only_allowed_components_on_own_entities() {
+        let mut app = app();
+        let permissions = WebCommandPermissions::default().allow_component::<Score>();
+        let webview = app.world.spawn(permissions).id();
+        let own = app
+            .world
+            .spawn((WebSpawned(webview), Score::default(), Secret::default()));
+        let own = own.id();
+        let other = app.world.spawn(Score::default()).id();
+
+        let remove = |entity: Entity, components: &[&str]| WebCommand::Remove {
+            entity: entity.to_bits(),
+            components: components.iter().map(|c| c.to_string()).collect(),
+        };
+        queue(&mut app.world, webview, 0, remove(own, &["Secret"]));
+        queue(&mut app.world, webview, 1, remove(other, &["Score"]));
+        app.update();
+        assert!(app.world.get::<Secret>(own).is_some());
+        assert!(app.world.get::<Score>(other).is_some());
+
+        queue(&mut app.world, webview, 2, remove(own, &["Score"]));
+        app.update();
+        assert!(app.world.get::<Score>(own).is_none());
+    }
+
+    #[test]
+    fn no_commands_without_permissions() {
+        let mut app = app();
+        let webview = app.world.spawn_empty().id();
+        let own = app.world.spawn(WebSpawned(webview)).id();
+        insert_score(&mut app, webview, own);
+        assert!(app.world.get::<Score>(own).is_none());
+    }
+
+    #[test]
+    fn despawn_keeps_foreign_descendants() {
+        let mut app = app();
+        let webview = app.world.spawn(WebCommandPermissions::default()).id();
+        let own_child = app.world.spawn(WebSpawned(webview)).id();
+        let own = app.world.spawn(WebSpawned(webview)).id();
+        app.world.entity_mut(own).add_child(own_child);
+        let foreign_child = app.world.spawn_empty().id();
+        let mixed = app.world.spawn(WebSpawned(webview)).id();
+        app.world.entity_mut(mixed).add_child(foreign_child);
+
+        let despawn = |entity: Entity| WebCommand::Despawn { entity: entity.to_bits() };
+        queue(&mut app.world, webview, 0, despawn(own));
+        queue(&mut app.world, webview, 1, despawn(mixed));
+        app.update();
+        assert!(app.world.get_entity(own).is_none());
+        assert!(app.world.get_entity(own_child).is_none());
+        assert!(app.world.get_entity(mixed).is_none());
+        assert!(app.world.get::<Parent>(foreign_child).is_none());
+    }
+}
@@ -0,0 +1,93 @@
+//! Errors of webviews, reported as [`WebViewError`] events.
+//!
+//! Two kinds of errors are reported: scripts bevy failed to run in a webview, and
+//! exceptions and unhandled promise rejections of the pages, posted by
+//! [`ERRORS_SCRIPT`].
+use bevy::prelude::*;
+
+use crate::outbox::FlushOutbox;
+
+/// Initialization script reporting uncaught errors of the page, it must run after
+/// [`crate::bridge::BRIDGE_SCRIPT`].
+///
+/// It listens to the `error` and `unhandledrejection` events rather than setting
+/// `window.onerror`, so that pages can still use it. `window.__bevy_report_error`
+/// reports errors caught elsewhere, such as in the scripts sent by the outbox.
+pub const ERRORS_SCRIPT: &str = r#"(() => {
+    const report = (message, source, line) => bevy.send({
+        type: "Error",
+        message: String(message),
+        source: source || null,
+        line: line || null,
+    });
+    window.__bevy_report_error = (error) => {
+        report(error?.stack ?? error, null, null);
+    };
+    window.addEventListener("error", (event) => {
+        report(event.error?.stack ?? event.message, event.filename, event.lineno);
+    });
+    window.addEventListener("unhandledrejection", (event) => {
+        report(`Unhandled rejection: ${event.reason?.stack ?? event.reason}`, null, null);
+    });
+})();"#;
+
+/// The source of the [`WebViewError`]s of scripts bevy failed to run.
+pub const EVALUATE_SCRIPT_SOURCE: &str = "evaluate_script";
+
+/// An error in the webview of entity `webview`.
+#[derive(Event, Debug, Clone)]
+pub struct WebViewError {
+    pub webview: Entity,
+    pub message: String,
+    /// The url of the script that threw, [`EVALUATE_SCRIPT_SOURCE`] if bevy couldn't
+    /// run a script, `None` when unknown.
+    pub source: Option<String>,
+    pub line: Option<u32>,
+}
+impl WebViewError {
+    /// `webview` couldn't run a script bevy sent it.
+    pub fn evaluate_script(webview: Entity, error: wry::Error) -> Self {
+        WebViewError {
+            webview,
+            message: error.to_string(),
+            source: Some(EVALUATE_SCRIPT_SOURCE.to_string()),
+            line: None,
+        }
+    }
+}
+
+/// Whether [`WebViewError`]s are also written to bevy's log, `true` by default.
+#[derive(Resource, Debug, Clone)]
+pub struct LogWebViewErrors(pub bool);
+impl Default for LogWebViewErrors {
+    fn default() -> Self {
+        LogWebViewErrors(true)
+    }
+}
+
+pub struct WebErrorsPlugin;
+impl Plugin for WebErrorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LogWebViewErrors>()
+            .add_event::<WebViewError>()
+            .add_systems(
+                Last,
+                log_errors
+                    .after(FlushOutbox)
+                    .run_if(|log: Res<LogWebViewErrors>| log.0),
+            );
+    }
+}
+
+fn log_errors(mut errors: EventReader<WebViewError>) {
+    for error in errors.iter() {
+        let WebViewError { webview, message, source, line } = error;
+        match (source, line) {
+            (Some(source), Some(line)) => {
+                error!("Webview {webview:?}: {message} ({source}:{line})")
+            }
+            (Some(source), None) => error!("Webview {webview:?}: {message} ({source})"),
+            (None, _) => error!("Webview {webview:?}: {message}"),
+        }
+    }
+}
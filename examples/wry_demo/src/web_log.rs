@@ -0,0 +1,276 @@
+//! Logs between bevy and webviews, in both directions.
+//!
+//! [`CONSOLE_SCRIPT`] forwards the `console` messages of pages to bevy's log, with the
+//! `webview` target and the webview entity as `webview` field.
+//!
+//! The other way, the [`WebLogLayer`] collects bevy's log records, and its
+//! [`WebLogPlugin`] streams them to the webviews with a [`WebLogStream`], on the
+//! `"log"` channel:
+//!
+//! ```js
+//! bevy.on("log", ({ level, target, message }) => console.log(level, target, message));
+//! ```
+//!
+//! A webview never gets the console messages it sent, so that writing the records to
+//! the console doesn't loop.
+//!
+//! Bevy's `LogPlugin` can't be given extra layers, so [`init_log`] sets up the same
+//! global subscriber as `LogPlugin` would, with the [`WebLogLayer`], before the app is
+//! built. `LogPlugin` stays in the app, and finds the subscriber already set.
+use std::collections::VecDeque;
+use std::fmt::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use bevy::log::tracing_subscriber::util::TryInitError;
+use bevy::log::tracing_subscriber::{self, layer::Context, prelude::*, EnvFilter, Layer};
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use bevy::utils::tracing::field::{Field, Visit};
+use bevy::utils::tracing::{self, Level, Subscriber};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::outbox::{FlushOutbox, Outbox, PushChannel};
+
+/// Initialization script forwarding `console` messages to bevy, it must run after
+/// [`crate::bridge::BRIDGE_SCRIPT`].
+pub const CONSOLE_SCRIPT: &str = r#"(() => {
+    const format = (arg) => {
+        if (typeof arg === "string") {
+            return arg;
+        } else if (arg instanceof Error) {
+            return arg.stack ?? String(arg);
+        }
+        try {
+            return JSON.stringify(arg) ?? String(arg);
+        } catch {
+            return String(arg);
+        }
+    };
+    for (const level of ["debug", "log", "info", "warn", "error"]) {
+        const original = console[level].bind(console);
+        console[level] = (...args) => {
+            original(...args);
+            bevy.send({ type: "Console", level, message: args.map(format).join(" ") });
+        };
+    }
+})();"#;
+
+/// The `console` method a message was logged with.
+#[derive(Deserialize, Debug, Clone, Copy, TS)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsoleLevel {
+    Debug,
+    Log,
+    Info,
+    Warn,
+    Error,
+}
+
+/// Write the console `message` of `webview` to bevy's log.
+pub fn forward_console(webview: Entity, level: ConsoleLevel, message: &str) {
+    match level {
+        ConsoleLevel::Debug => debug!(target: "webview", ?webview, "{message}"),
+        ConsoleLevel::Log | ConsoleLevel::Info => info!(target: "webview", ?webview, "{message}"),
+        ConsoleLevel::Warn => warn!(target: "webview", ?webview, "{message}"),
+        ConsoleLevel::Error => error!(target: "webview", ?webview, "{message}"),
+    }
+}
+
+/// A log record, pushed on the `"log"` channel.
+#[derive(Serialize, Debug, TS)]
+pub struct WebLogRecord {
+    #[ts(type = r#""TRACE" | "DEBUG" | "INFO" | "WARN" | "ERROR""#)]
+    level: String,
+    target: String,
+    message: String,
+}
+impl PushChannel for WebLogRecord {
+    const CHANNEL: &'static str = "log";
+}
+
+/// Maximum number of log records waiting to be streamed, older ones are dropped.
+const MAX_PENDING_RECORDS: usize = 1000;
+
+struct Record {
+    level: Level,
+    target: String,
+    message: String,
+    /// The `webview` field, formatted with `Debug`.
+    webview: Option<String>,
+}
+
+/// Log records waiting to be streamed, shared with the [`WebLogLayer`].
+#[derive(Resource, Clone, Default)]
+struct PendingRecords(Arc<Mutex<VecDeque<Record>>>);
+
+/// The `tracing` layer collecting the records streamed by its [`WebLogPlugin`].
+pub struct WebLogLayer(PendingRecords);
+impl WebLogLayer {
+    /// The layer, to add to the global subscriber, and the plugin streaming its records.
+    pub fn new() -> (WebLogLayer, WebLogPlugin) {
+        let records = PendingRecords::default();
+        (WebLogLayer(records.clone()), WebLogPlugin(records))
+    }
+}
+impl<S: Subscriber> Layer<S> for WebLogLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let record = Record {
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            message: visitor.message + &visitor.fields,
+            webview: visitor.webview,
+        };
+        let mut records = (self.0).0.lock().unwrap();
+        if records.len() >= MAX_PENDING_RECORDS {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+}
+
+/// Formats the message of an event, followed by its other fields as `name=value`.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+    webview: Option<String>,
+}
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            if field.name() == "webview" {
+                self.webview = Some(format!("{value:?}"));
+            }
+            let _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+}
+
+/// Stream bevy's log records to the webview of this entity.
+#[derive(Component, Debug, Clone)]
+pub struct WebLogStream {
+    /// The most verbose level streamed.
+    pub level: Level,
+    /// Only stream records with a target starting with one of these, all if empty.
+    pub targets: Vec<String>,
+    /// Also stream the console messages of other webviews, with the `webview` target.
+    ///
+    /// Off by default: two webviews writing the records they get to their console
+    /// would send each other's messages back and forth.
+    pub webview_logs: bool,
+}
+impl WebLogStream {
+    pub fn new(level: Level) -> Self {
+        WebLogStream { level, targets: Vec::new(), webview_logs: false }
+    }
+    pub fn with_target(mut self, target: impl Into<String>) -> Self {
+        self.targets.push(target.into());
+        self
+    }
+    pub fn with_webview_logs(mut self) -> Self {
+        self.webview_logs = true;
+        self
+    }
+    /// Whether to stream `record` to `webview`.
+    fn accepts(&self, record: &Record, webview: Entity) -> bool {
+        if record.target == "webview" {
+            let own = record.webview.as_deref() == Some(&format!("{webview:?}"));
+            if own || !self.webview_logs {
+                return false;
+            }
+        }
+        let target_matches = || {
+            let mut targets = self.targets.iter();
+            self.targets.is_empty() || targets.any(|target| record.target.starts_with(target))
+        };
+        record.level <= self.level && target_matches()
+    }
+}
+
+/// Set up the global `tracing` subscriber as `log` would, with a [`WebLogLayer`], and
+/// return the plugin streaming its records.
+///
+/// Call this before building the app, and keep `log` in it: it then finds the
+/// subscriber set, which it reports at the `warn` level, filtered out here.
+///
+/// Fails if a global subscriber is already set.
+pub fn init_log(log: &LogPlugin) -> Result<WebLogPlugin, TryInitError> {
+    let (layer, plugin) = WebLogLayer::new();
+    let default_filter = format!("{},{}", log.level, log.filter);
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&default_filter))
+        .unwrap()
+        .add_directive("bevy_log=error".parse().unwrap());
+    let subscriber = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::Layer::default().with_writer(std::io::stderr))
+        .with(layer);
+    subscriber.try_init()?;
+    Ok(plugin)
+}
+
+/// Streams the records of its [`WebLogLayer`] to webviews with a [`WebLogStream`].
+pub struct WebLogPlugin(PendingRecords);
+impl Plugin for WebLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.0.clone())
+            .add_systems(Last, stream_logs.before(FlushOutbox));
+    }
+}
+
+fn stream_logs(
+    records: Res<PendingRecords>,
+    streams: Query<(Entity, &WebLogStream)>,
+    mut outbox: ResMut<Outbox>,
+) {
+    let records = std::mem::take(&mut *records.0.lock().unwrap());
+    for record in &records {
+        for (webview, stream) in &streams {
+            if !stream.accepts(record, webview) {
+                continue;
+            }
+            let data = WebLogRecord {
+                level: record.level.as_str().to_string(),
+                target: record.target.clone(),
+                message: record.message.clone(),
+            };
+            outbox.push(webview, &data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use bevy::utils::tracing::Level;
+
+    use super::{Record, WebLogStream};
+
+    fn record(target: &str, webview: Option<Entity>) -> Record {
+        Record {
+            level: Level::INFO,
+            target: target.to_string(),
+            message: String::new(),
+            webview: webview.map(|webview| format!("{webview:?}")),
+        }
+    }
+
+    #[test]
+    fn console_messages_dont_loop() {
+        let (own, other) = (Entity::from_raw(0), Entity::from_raw(1));
+        let stream = WebLogStream::new(Level::INFO);
+        assert!(stream.accepts(&record("wry_demo", None), own));
+        assert!(!stream.accepts(&record("webview", Some(own)), own));
+        assert!(!stream.accepts(&record("webview", Some(other)), own));
+
+        let stream = stream.with_webview_logs();
+        assert!(!stream.accepts(&record("webview", Some(own)), own));
+        assert!(stream.accepts(&record("webview", Some(other)), own));
+    }
+}
@@ -0,0 +1,305 @@
+//! Crashes and hangs of the web processes rendering webviews, and their recovery.
+//!
+//! The webview engine reports when the web process of a webview terminates or
+//! stops responding, these are sent as [`WebProcessEvent`]s. The page of a crashed
+//! webview is blank until reloaded, with [`ReloadWebView`], or automatically
+//! according to its [`WebProcessRecovery`]. Reloading starts a new web process
+//! loading the last url of the webview, which runs the initialization scripts again.
+//!
+//! This is only reported on linux.
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::bridge::{Request, WrySender};
+use crate::webview::WryWebviews;
+
+/// Something happened to the web process of `webview`.
+#[derive(Event, Debug, Clone)]
+pub struct WebProcessEvent {
+    pub webview: Entity,
+    pub kind: WebProcessEventKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebProcessEventKind {
+    /// The web process is gone, the webview shows nothing until reloaded.
+    Terminated(TerminationReason),
+    /// The web process doesn't answer the engine anymore.
+    Unresponsive,
+    /// The web process answers again after being [`WebProcessEventKind::Unresponsive`].
+    Responsive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminationReason {
+    Crashed,
+    ExceededMemoryLimit,
+    /// Terminated on purpose, such as when it was unresponsive for too long.
+    Terminated,
+}
+
+/// Reload the page of `webview` in a new web process, for example after it crashed.
+#[derive(Event, Debug, Clone)]
+pub struct ReloadWebView {
+    pub webview: Entity,
+}
+
+/// How the webview of this entity recovers from web process failures, webviews
+/// without it use the default.
+#[derive(Component, Debug, Clone)]
+pub struct WebProcessRecovery {
+    /// Reload the webview when its web process terminates.
+    pub auto_reload: bool,
+    /// Stop reloading automatically after this many reloads within `reload_window`,
+    /// so that a page crashing on load doesn't reload forever.
+    pub max_reloads: u32,
+    /// The time over which automatic reloads are counted, from the first one.
+    pub reload_window: Duration,
+    /// Terminate the web process when it stays unresponsive for this long, which
+    /// reloads it with `auto_reload`. `None` to wait for it forever.
+    pub unresponsive_timeout: Option<Duration>,
+}
+impl Default for WebProcessRecovery {
+    fn default() -> Self {
+        WebProcessRecovery {
+            auto_reload: true,
+            max_reloads: 3,
+            reload_window: Duration::from_secs(60),
+            unresponsive_timeout: Some(Duration::from_secs(10)),
+        }
+    }
+}
+
+/// Number of automatic reloads of a webview, since `since` in [`Time::elapsed`].
+struct AutoReloads {
+    count: u32,
+    since: Duration,
+}
+impl AutoReloads {
+    /// Count a reload at `now` if `recovery` allows one more, the count restarts
+    /// once its window is over.
+    fn try_reload(&mut self, now: Duration, recovery: &WebProcessRecovery) -> bool {
+        if now - self.since >= recovery.reload_window {
+            *self = AutoReloads { count: 0, since: now };
+        }
+        if self.count >= recovery.max_reloads {
+            return false;
+        }
+        self.count += 1;
+        true
+    }
+}
+
+/// When the web process of a webview became unresponsive, in [`Time::elapsed`].
+#[derive(Component)]
+struct UnresponsiveSince(Duration);
+
+pub struct WebProcessPlugin;
+impl Plugin for WebProcessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<WebProcessEvent>()
+            .add_event::<ReloadWebView>()
+            .add_systems(
+                Update,
+                (recover, terminate_unresponsive, reload_webviews).chain(),
+            );
+    }
+}
+
+/// Report the web process events of the webview of `entity` through `sender`.
+#[cfg(target_os = "linux")]
+pub fn connect(webview: &wry::webview::WebView, entity: Entity, sender: &WrySender) {
+    use webkit2gtk::{WebProcessTerminationReason, WebViewExt};
+    use wry::webview::WebviewExtUnix;
+
+    let (terminations, responsiveness) = (sender.clone(), sender.clone());
+    let webview = webview.webview();
+    webview.connect_web_process_terminated(move |_, reason| {
+        let reason = match reason {
+            WebProcessTerminationReason::ExceededMemoryLimit => {
+                TerminationReason::ExceededMemoryLimit
+            }
+            WebProcessTerminationReason::TerminatedByApi => TerminationReason::Terminated,
+            _ => TerminationReason::Crashed,
+        };
+        send(
+            &terminations,
+            entity,
+            WebProcessEventKind::Terminated(reason),
+        );
+    });
+    webview.connect_is_web_process_responsive_notify(move |webview| {
+        let kind = match webview.is_web_process_responsive() {
+            true => WebProcessEventKind::Responsive,
+            false => WebProcessEventKind::Unresponsive,
+        };
+        send(&responsiveness, entity, kind);
+    });
+}
+#[cfg(not(target_os = "linux"))]
+pub fn connect(_: &wry::webview::WebView, _: Entity, _: &WrySender) {}
+
+fn send(sender: &WrySender, entity: Entity, kind: WebProcessEventKind) {
+    // The receiver is only dropped when the app exits.
+    let _ = sender.send((entity, None, Request::WebProcess(kind)));
+}
+
+/// Send the [`WebProcessEvent`] of `kind`, and log it.
+pub fn emit(world: &mut World, webview: Entity, kind: WebProcessEventKind) {
+    match kind {
+        WebProcessEventKind::Terminated(reason) => {
+            error!("The web process of {webview:?} terminated: {reason:?}")
+        }
+        WebProcessEventKind::Unresponsive => {
+            warn!("The web process of {webview:?} is unresponsive")
+        }
+        WebProcessEventKind::Responsive => {
+            info!("The web process of {webview:?} is responsive again")
+        }
+    }
+    world.send_event(WebProcessEvent { webview, kind });
+}
+
+/// Apply the [`WebProcessRecovery`] of webviews to their web process events.
+fn recover(
+    mut commands: Commands,
+    mut events: EventReader<WebProcessEvent>,
+    recoveries: Query<Option<&WebProcessRecovery>>,
+    mut auto_reloads: Local<HashMap<Entity, AutoReloads>>,
+    mut reloads: EventWriter<ReloadWebView>,
+    time: Res<Time>,
+) {
+    // Kept here rather than in a component, so that a second termination in the same
+    // frame sees the first reload.
+    auto_reloads.retain(|webview, _| recoveries.contains(*webview));
+    for &WebProcessEvent { webview, kind } in events.iter() {
+        let Some(mut entity) = commands.get_entity(webview) else {
+            continue;
+        };
+        match kind {
+            WebProcessEventKind::Terminated(_) => {
+                entity.remove::<UnresponsiveSince>();
+                let Ok(recovery) = recoveries.get(webview) else {
+                    continue;
+                };
+                let default_recovery = WebProcessRecovery::default();
+                let recovery = recovery.unwrap_or(&default_recovery);
+                if !recovery.auto_reload {
+                    continue;
+                }
+                let now = time.elapsed();
+                let reloaded = auto_reloads
+                    .entry(webview)
+                    .or_insert(AutoReloads { count: 0, since: now });
+                if !reloaded.try_reload(now, recovery) {
+                    let count = reloaded.count;
+                    warn!("Not reloading {webview:?}, it was already reloaded {count} times");
+                    continue;
+                }
+                reloads.send(ReloadWebView { webview });
+            }
+            WebProcessEventKind::Unresponsive => {
+                entity.insert(UnresponsiveSince(time.elapsed()));
+            }
+            WebProcessEventKind::Responsive => {
+                entity.remove::<UnresponsiveSince>();
+            }
+        }
+    }
+}
+
+/// Terminate the web processes unresponsive for longer than their
+/// [`WebProcessRecovery::unresponsive_timeout`].
+fn terminate_unresponsive(
+    mut commands: Commands,
+    unresponsive: Query<(Entity, &UnresponsiveSince, Option<&WebProcessRecovery>)>,
+    webviews: NonSend<WryWebviews>,
+    time: Res<Time>,
+) {
+    for (entity, since, recovery) in &unresponsive {
+        let timeout = match recovery {
+            Some(recovery) => recovery.unresponsive_timeout,
+            None => WebProcessRecovery::default().unresponsive_timeout,
+        };
+        let timed_out = timeout.map_or(false, |timeout| time.elapsed() - since.0 >= timeout);
+        let Some(webview) = webviews.get(entity).filter(|_| timed_out) else {
+            continue;
+        };
+        warn!("Terminating the unresponsive web process of {entity:?}");
+        terminate(webview);
+        commands.entity(entity).remove::<UnresponsiveSince>();
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn terminate(webview: &wry::webview::WebView) {
+    use webkit2gtk::WebViewExt;
+    use wry::webview::WebviewExtUnix;
+
+    webview.webview().terminate_web_process();
+}
+#[cfg(not(target_os = "linux"))]
+fn terminate(_: &wry::webview::WebView) {}
+
+/// Load the last url of the webviews to reload.
+///
+/// A `window.location.reload()` wouldn't do, there is no page to run it after a crash.
+fn reload_webviews(mut reloads: EventReader<ReloadWebView>, webviews: NonSend<WryWebviews>) {
+    for ReloadWebView { webview: entity } in reloads.iter() {
+        let Some(webview) = webviews.get(*entity) else {
+            continue;
+        };
+        let url = webview.url().to_string();
+        info!("Reloading {entity:?} at {url}");
+        webview.load_url(&url);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{AutoReloads, WebProcessRecovery};
+
+    fn recovery() -> WebProcessRecovery {
+        WebProcessRecovery {
+            max_reloads: 2,
+            reload_window: Duration::from_secs(60),
+            ..Default::default()
+        }
+    }
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn stops_after_max_reloads() {
+        let recovery = recovery();
+        let mut reloads = AutoReloads { count: 0, since: secs(0) };
+        assert!(reloads.try_reload(secs(1), &recovery));
+        assert!(reloads.try_reload(secs(2), &recovery));
+        assert!(!reloads.try_reload(secs(3), &recovery));
+        assert_eq!(reloads.count, 2);
+    }
+
+    #[test]
+    fn count_restarts_after_the_window() {
+        let recovery = recovery();
+        let mut reloads = AutoReloads { count: 2, since: secs(0) };
+        assert!(!reloads.try_reload(secs(59), &recovery));
+        assert!(reloads.try_reload(secs(60), &recovery));
+        assert_eq!((reloads.count, reloads.since), (1, secs(60)));
+    }
+
+    #[test]
+    fn terminations_in_one_frame_are_counted() {
+        let recovery = recovery();
+        let mut reloads = AutoReloads { count: 1, since: secs(0) };
+        let now = secs(10);
+        assert!(reloads.try_reload(now, &recovery));
+        assert!(!reloads.try_reload(now, &recovery));
+    }
+}
@@ -0,0 +1,213 @@
+//! Push the state of resources and components to the web UI as they change.
+//!
+//! Mark a `Serialize` resource or component as web-synced with [`WebSyncAppExt`].
+//! It is sent to all webviews whenever it changes, and pages read it as a store
+//! named after its short type name, with the `bevy.subscribe` function defined
+//! by [`SYNC_SCRIPT`]:
+//!
+//! ```js
+//! const unsubscribe = bevy.subscribe("Inventory", (inventory) => render(inventory));
+//! ```
+//!
+//! The callback is called with the current value on subscribe, then on every change.
+//! The value of a synced resource is the serialized resource, or `null` if it doesn't
+//! exist. The value of a synced component is an object with the component of each
+//! entity, by `Entity::to_bits` value.
+use bevy::prelude::*;
+use bevy::utils::{get_short_name, HashMap};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use ts_rs::TS;
+
+use crate::outbox::{FlushOutbox, Outbox, PushChannel};
+use crate::webview::WryWebviews;
+
+/// Initialization script defining `bevy.subscribe`, it must run after
+/// [`crate::bridge::BRIDGE_SCRIPT`].
+pub const SYNC_SCRIPT: &str = r#"(() => {
+    const stores = new Map();
+    const subscribers = new Map();
+    function update(store, value) {
+        stores.set(store, value);
+        for (const callback of subscribers.get(store) ?? []) {
+            callback(value);
+        }
+    }
+    bevy.on("sync", ({ store, value, patch }) => {
+        if (patch === undefined) {
+            update(store, value);
+        } else if (stores.has(store)) {
+            const entities = { ...stores.get(store) };
+            for (const entity of patch.removed) {
+                delete entities[entity];
+            }
+            update(store, Object.assign(entities, patch.changed));
+        }
+    });
+    bevy.subscribe = (store, callback) => {
+        if (!subscribers.has(store)) {
+            subscribers.set(store, new Set());
+        }
+        subscribers.get(store).add(callback);
+        if (stores.has(store)) {
+            callback(stores.get(store));
+        } else {
+            bevy.call("Subscribe", { store }).then((value) => {
+                // A change may have been pushed while waiting for the answer.
+                if (!stores.has(store)) {
+                    update(store, value);
+                }
+            });
+        }
+        return () => subscribers.get(store).delete(callback);
+    };
+})();"#;
+
+/// A store change, pushed on the `"sync"` channel.
+#[derive(Serialize, Debug, TS)]
+#[serde(untagged)]
+pub enum SyncPush {
+    /// The new value of a synced resource.
+    Value {
+        store: String,
+        #[ts(type = "unknown")]
+        value: Value,
+    },
+    /// The changes of a synced component.
+    Patch { store: String, patch: SyncPatch },
+}
+impl PushChannel for SyncPush {
+    const CHANNEL: &'static str = "sync";
+}
+
+/// The components changed and removed since the last [`SyncPush::Patch`].
+#[derive(Serialize, Debug, TS)]
+pub struct SyncPatch {
+    /// The changed components, by `Entity::to_bits` value.
+    #[ts(type = "Record<string, unknown>")]
+    changed: Map<String, Value>,
+    removed: Vec<String>,
+}
+
+/// Snapshot functions of the synced stores, by store name.
+#[derive(Resource, Default)]
+struct SyncedStores(HashMap<String, fn(&mut World) -> Result<Value, String>>);
+
+/// Mark resources and components as synced to the web UI.
+pub trait WebSyncAppExt {
+    /// Push `R` to all webviews whenever it changes.
+    fn sync_resource_to_web<R: Resource + Serialize>(&mut self) -> &mut Self;
+    /// Push the changes of `C` to all webviews, as a map of entities to components.
+    fn sync_component_to_web<C: Component + Serialize>(&mut self) -> &mut Self;
+}
+impl WebSyncAppExt for App {
+    fn sync_resource_to_web<R: Resource + Serialize>(&mut self) -> &mut Self {
+        self.init_resource::<SyncedStores>();
+        let mut stores = self.world.resource_mut::<SyncedStores>();
+        stores.0.insert(store_name::<R>(), snapshot_resource::<R>);
+        self.add_systems(Last, push_resource::<R>.before(FlushOutbox))
+    }
+    fn sync_component_to_web<C: Component + Serialize>(&mut self) -> &mut Self {
+        self.init_resource::<SyncedStores>();
+        let mut stores = self.world.resource_mut::<SyncedStores>();
+        stores.0.insert(store_name::<C>(), snapshot_component::<C>);
+        self.add_systems(Last, push_component::<C>.before(FlushOutbox))
+    }
+}
+
+fn store_name<T>() -> String {
+    get_short_name(std::any::type_name::<T>())
+}
+
+/// Answer the `bevy.subscribe` of `store` from `webview`, with the current value of the store.
+pub fn handle_subscribe(world: &mut World, webview: Entity, id: u64, store: &str) {
+    let snapshot = world
+        .get_resource::<SyncedStores>()
+        .and_then(|stores| stores.0.get(store).copied());
+    let result = match snapshot {
+        Some(snapshot) => snapshot(world),
+        None => Err(format!("{store} is not synced to webviews")),
+    };
+    world.resource_mut::<Outbox>().reply(webview, id, result);
+}
+
+fn snapshot_resource<R: Resource + Serialize>(world: &mut World) -> Result<Value, String> {
+    let value = world.get_resource::<R>().map(serde_json::to_value);
+    value
+        .transpose()
+        .map(Option::unwrap_or_default)
+        .map_err(|err| err.to_string())
+}
+
+fn snapshot_component<C: Component + Serialize>(world: &mut World) -> Result<Value, String> {
+    let mut query = world.query::<(Entity, &C)>();
+    let components = query
+        .iter(world)
+        .map(|(entity, component)| {
+            Ok((
+                entity.to_bits().to_string(),
+                serde_json::to_value(component)?,
+            ))
+        })
+        .collect::<Result<Map<_, _>, serde_json::Error>>();
+    components.map(Value::Object).map_err(|err| err.to_string())
+}
+
+fn push_resource<R: Resource + Serialize>(
+    resource: Option<Res<R>>,
+    mut existed: Local<bool>,
+    mut outbox: ResMut<Outbox>,
+    webviews: NonSend<WryWebviews>,
+) {
+    let exists = resource.is_some();
+    let changed = resource
+        .as_ref()
+        .map_or(*existed, |resource| resource.is_changed());
+    *existed = exists;
+    if !changed {
+        return;
+    }
+    let value = match resource.map(|resource| serde_json::to_value(&*resource)) {
+        Some(Ok(value)) => value,
+        Some(Err(err)) => {
+            warn!("Could not sync {} to webviews: {err}", store_name::<R>());
+            return;
+        }
+        None => Value::Null,
+    };
+    let store = store_name::<R>();
+    let data = SyncPush::Value { store: store.clone(), value };
+    for (webview, _) in webviews.iter() {
+        // Only the latest value matters.
+        outbox.push_latest(webview, &store, &data);
+    }
+}
+
+fn push_component<C: Component + Serialize>(
+    changed: Query<(Entity, &C), Changed<C>>,
+    mut removed: RemovedComponents<C>,
+    mut outbox: ResMut<Outbox>,
+    webviews: NonSend<WryWebviews>,
+) {
+    let mut changed_components = Map::new();
+    for (entity, component) in &changed {
+        match serde_json::to_value(component) {
+            Ok(value) => {
+                changed_components.insert(entity.to_bits().to_string(), value);
+            }
+            Err(err) => warn!("Could not sync {} to webviews: {err}", store_name::<C>()),
+        }
+    }
+    let removed: Vec<String> = removed
+        .iter()
+        .map(|entity| entity.to_bits().to_string())
+        .collect();
+    if changed_components.is_empty() && removed.is_empty() {
+        return;
+    }
+    let patch = SyncPatch { changed: changed_components, removed };
+    let data = SyncPush::Patch { store: store_name::<C>(), patch };
+    for (webview, _) in webviews.iter() {
+        outbox.push(webview, &data);
+    }
+}
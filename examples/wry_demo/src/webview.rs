@@ -1,8 +1,169 @@
+//! Webviews living in their own window, following a bevy window.
+//!
+//! Embedding a webview in the bevy window doesn't work everywhere yet, so instead
+//! the webview gets its own undecorated transparent window, kept on top of the
+//! content area of its host bevy window.
 use bevy::prelude::*;
-use bevy_tao::TaoWindows;
+use bevy::utils::HashMap;
+use bevy::window::WindowClosed;
+use bevy_winit_gtk::WinitWindows;
+use wry::application::event_loop::EventLoopWindowTarget;
+use wry::application::window::{Window as WinitWindow, WindowBuilder};
+use wry::webview::WebView;
 
-fn primary_window_webview(tao_windows: NonSend<TaoWindows>) {
-    if !tao_windows.is_changed() {
-        return;
+/// A webview whose window follows the bevy window `0`.
+///
+/// The webview window is kept over the host window's content area, has the same size,
+/// visibility and minimized state, and is closed together with it.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct FollowWindow(pub Entity);
+
+/// Zoom level of the webview page, following the host's scale factor override.
+///
+/// This is the size of a CSS pixel in logical pixels of the webview window.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct WebViewZoom(pub f64);
+
+/// All webviews, by entity.
+///
+/// This is a `NonSend` resource, since `WebView` is not `Send`.
+#[derive(Default)]
+pub struct WryWebviews {
+    webviews: HashMap<Entity, WebView>,
+}
+impl WryWebviews {
+    pub fn get(&self, entity: Entity) -> Option<&WebView> {
+        self.webviews.get(&entity)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &WebView)> {
+        self.webviews
+            .iter()
+            .map(|(entity, webview)| (*entity, webview))
+    }
+    pub fn insert(&mut self, entity: Entity, webview: WebView) {
+        self.webviews.insert(entity, webview);
+    }
+    pub fn remove(&mut self, entity: Entity) -> Option<WebView> {
+        self.webviews.remove(&entity)
+    }
+}
+
+pub struct WebViewPlugin;
+impl Plugin for WebViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_non_send_resource::<WryWebviews>()
+            .add_systems(Last, (close_orphan_followers, sync_followers).chain());
+    }
+}
+
+/// Create the window of a webview that will follow `host`.
+///
+/// The window starts hidden, [`WebViewPlugin`] shows it once it is in place.
+pub fn follower_window(event_loop: &EventLoopWindowTarget<()>, host: &WinitWindow) -> WinitWindow {
+    let window = WindowBuilder::new()
+        .with_transparent(true)
+        .with_decorations(false)
+        .with_inner_size(host.inner_size())
+        .with_visible(false)
+        .build(event_loop)
+        .unwrap();
+
+    // Keep the webview window on top of its host, and minimize it with the host.
+    #[cfg(target_os = "linux")]
+    {
+        use gtk::prelude::GtkWindowExt;
+        use wry::application::platform::unix::WindowExtUnix;
+
+        window
+            .gtk_window()
+            .set_transient_for(Some(host.gtk_window()));
+    }
+    window
+}
+
+/// Make `entity` a webview following the bevy window `host`.
+///
+/// `webview` should be built from a window created with [`follower_window`].
+pub fn insert_follower(world: &mut World, entity: Entity, host: Entity, webview: WebView) {
+    world
+        .entity_mut(entity)
+        .insert((Name::new("Webview"), FollowWindow(host)));
+
+    let webview_window = webview.window().id();
+    world
+        .non_send_resource_mut::<WinitWindows>()
+        .attach_window(webview_window, host);
+    world
+        .non_send_resource_mut::<WryWebviews>()
+        .insert(entity, webview);
+}
+
+/// Close the webviews of closed windows.
+fn close_orphan_followers(
+    mut commands: Commands,
+    mut closed: EventReader<WindowClosed>,
+    followers: Query<(Entity, &FollowWindow)>,
+    mut webviews: NonSendMut<WryWebviews>,
+    mut winit_windows: NonSendMut<WinitWindows>,
+) {
+    for closed in closed.iter() {
+        let orphans = followers
+            .iter()
+            .filter(|(_, follow)| follow.0 == closed.window);
+        for (entity, _) in orphans {
+            if let Some(webview) = webviews.remove(entity) {
+                winit_windows.detach_window(webview.window().id());
+            }
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Keep webview windows on top of their host window.
+fn sync_followers(
+    mut commands: Commands,
+    followers: Query<(Entity, &FollowWindow, Option<&WebViewZoom>)>,
+    hosts: Query<&Window>,
+    webviews: NonSend<WryWebviews>,
+    winit_windows: NonSend<WinitWindows>,
+) {
+    for (entity, follow, zoom) in &followers {
+        let (Some(webview), Some(host)) =
+            (webviews.get(entity), winit_windows.get_window(follow.0))
+        else {
+            continue;
+        };
+        let window = webview.window();
+
+        if let Ok(position) = host.inner_position() {
+            if window.outer_position().ok() != Some(position) {
+                window.set_outer_position(position);
+            }
+        }
+        let size = host.inner_size();
+        if window.inner_size() != size {
+            window.set_inner_size(size);
+        }
+
+        let minimized = host.is_minimized().unwrap_or(false);
+        if window.is_minimized() != Some(minimized) {
+            window.set_minimized(minimized);
+        }
+        let visible = host.is_visible().unwrap_or(true);
+        if window.is_visible() != Some(visible) {
+            window.set_visible(visible);
+        }
+
+        // The webview window gets the backend scale factor from the OS like its host,
+        // only the bevy override needs to be applied.
+        let Ok(host_window) = hosts.get(follow.0) else {
+            continue;
+        };
+        let resolution = &host_window.resolution;
+        let new_zoom = resolution.scale_factor() / resolution.base_scale_factor();
+        if zoom.map(|zoom| zoom.0) != Some(new_zoom) {
+            webview.zoom(new_zoom);
+            commands.entity(entity).insert(WebViewZoom(new_zoom));
+        }
     }
 }
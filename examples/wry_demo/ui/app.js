@@ -0,0 +1,101 @@
+// A tiny single page app. Unknown paths are served `index.html` by the
+// `ui://` protocol, so the page content is picked from `location.pathname`.
+const PAGES = {
+  "/": `<h1>Home</h1><p>This UI is bundled with the game, it works offline.</p>
+    <p>Selected link: <span id="selected-link"></span></p>`,
+  "/inventory": `<h1>Inventory</h1><img src="bevy://bevy_icon.png" width="64" alt="Bevy icon">`,
+  "/settings": `<h1>Settings</h1><button id="test-links">Spawn test links</button>`,
+  "/credits": "<h1>Credits</h1><p>Made with bevy and wry.</p>",
+  "/inspector": `<h1>Inspector</h1><div id="entities"></div><div id="components"></div>`,
+  "/logs": `<h1>Logs</h1><pre id="log"></pre>`,
+};
+
+// Pages that need more than HTML.
+const SCRIPTS = {
+  "/": home,
+  "/settings": settings,
+  "/inspector": inspector,
+  "/logs": logs,
+};
+
+function render() {
+  const page = PAGES[location.pathname] ?? "<h1>Not found</h1>";
+  document.getElementById("page").innerHTML = page;
+  SCRIPTS[location.pathname]?.();
+}
+
+// Show the link highlighted in bevy, synced from the `SelectedLink` resource.
+function home() {
+  bevy.subscribe("SelectedLink", (link) => {
+    document.getElementById("selected-link").textContent = link ?? "none";
+  });
+}
+
+// Replace the bevy-balls with links sent from here, through `bevy.commands`.
+function settings() {
+  document.getElementById("test-links").onclick = () => {
+    const links = ["https://bevyengine.org", "https://github.com/tauri-apps/wry"];
+    bevy.commands.sendEvent("NewPage", { links });
+  };
+}
+
+// List entities, and show the components of the clicked one as editable JSON.
+async function inspector() {
+  const list = document.getElementById("entities");
+  const panel = document.getElementById("components");
+  let unwatch = null;
+  for (const { entity, name } of await bevy.inspector.entities()) {
+    const button = document.createElement("button");
+    button.textContent = name ?? `Entity ${entity}`;
+    button.onclick = async () => {
+      unwatch?.();
+      panel.replaceChildren();
+      const editors = new Map();
+      unwatch = await bevy.inspector.watch(entity, ({ changed, removed, despawned }) => {
+        if (despawned) {
+          panel.textContent = "Despawned";
+          return;
+        }
+        for (const component of removed) {
+          editors.get(component)?.remove();
+          editors.delete(component);
+        }
+        for (const [component, value] of Object.entries(changed ?? {})) {
+          if (!editors.has(component)) {
+            editors.set(component, componentEditor(entity, component));
+            panel.append(editors.get(component));
+          }
+          const text = editors.get(component).querySelector("textarea");
+          if (document.activeElement !== text) {
+            text.value = JSON.stringify(value, null, 2);
+          }
+        }
+      });
+    };
+    list.append(button);
+  }
+}
+
+function componentEditor(entity, component) {
+  const editor = document.createElement("details");
+  editor.innerHTML = `<summary></summary><textarea rows="6" cols="40"></textarea><button>Apply</button>`;
+  editor.querySelector("summary").textContent = component;
+  editor.querySelector("button").onclick = () => {
+    const value = JSON.parse(editor.querySelector("textarea").value);
+    bevy.inspector.set(entity, component, value).catch((err) => alert(err.message));
+  };
+  return editor;
+}
+
+// Show bevy's log records, streamed to this webview by its `WebLogStream`.
+function logs() {
+  const log = document.getElementById("log");
+  bevy.on("log", ({ level, target, message }) => {
+    log.append(`${level} ${target}: ${message}\n`);
+    while (log.childNodes.length > 200) {
+      log.firstChild.remove();
+    }
+  });
+}
+
+window.addEventListener("DOMContentLoaded", render);
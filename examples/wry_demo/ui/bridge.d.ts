@@ -0,0 +1,113 @@
+// Generated from the Rust bridge types by `wry_demo::typescript`, do not edit.
+// Regenerate with `make bindings`.
+
+export type Request = { "type": "InteractiveRegions", regions: Array<Region>, } | { "type": "Input", input: DomInput, } | { "type": "Dirty" } | { "type": "Inspector", id: number, } & InspectorOp | { "type": "Command", id: number, } & WebCommand | { "type": "Subscribe", id: number, store: string, } | { "type": "Error", message: string, source: string | null, line: number | null, } | { "type": "DomMatches", watch: string, added: Array<DomMatch>, changed: Array<DomMatch>, removed: Array<number>, } | { "type": "Console", level: ConsoleLevel, message: string, };
+
+export type BevyEvent = { "NavigateToPage": string };
+
+export interface Region { x: number, y: number, width: number, height: number, }
+
+export type DomInput = { "type": "Key", state: DomButtonState, code: string, handled: boolean, } | { "type": "Button", state: DomButtonState, button: number, handled: boolean, } | { "type": "Move", x: number, y: number, handled: boolean, } | { "type": "Wheel", x: number, y: number, line: boolean, handled: boolean, };
+
+export type DomButtonState = "Pressed" | "Released";
+
+export type InspectorOp = { "op": "Entities" } | { "op": "Components", entity: number, } | { "op": "Set", entity: number, component: string, value: unknown, } | { "op": "Watch", entity: number, } | { "op": "Unwatch", entity: number, };
+
+export interface InspectorEntity { entity: number, name: string | null, parent: number | null, }
+
+export interface InspectorDiff { entity: number, changed: Record<string, unknown>, removed: Array<string>, despawned: boolean, }
+
+export type WebCommand = { "command": "Spawn", components: Record<string, unknown>, } | { "command": "Insert", entity: number, components: Record<string, unknown>, } | { "command": "Remove", entity: number, components: Array<string>, } | { "command": "Despawn", entity: number, } | { "command": "SendEvent", event: string, value: unknown, };
+
+export type ConsoleLevel = "debug" | "log" | "info" | "warn" | "error";
+
+export interface DomMatch { id: number, attributes: Record<string, string>, text: string | null, rect: Region | null, }
+
+export interface WebLogRecord { level: "TRACE" | "DEBUG" | "INFO" | "WARN" | "ERROR", target: string, message: string, }
+
+export type SyncPush = { store: string, value: unknown, } | { store: string, patch: SyncPatch, };
+
+export interface SyncPatch { changed: Record<string, unknown>, removed: Array<string>, }
+
+type DistributiveOmit<T, K extends PropertyKey> = T extends unknown ? Omit<T, K> : never;
+
+/** The requests answered by `bevy.call`. */
+export type CallType = Extract<Request, { id: number }>["type"];
+
+/** The arguments of the `bevy.call` of `T`. */
+export type CallArgs<T extends CallType> = DistributiveOmit<Extract<Request, { type: T }>, "type" | "id">;
+
+/** The answer to the `bevy.call` of `T` with the arguments `A`. */
+export type CallResult<T extends CallType, A> =
+  T extends "Inspector" ? (
+    A extends { op: "Entities" } ? Array<InspectorEntity> :
+    A extends { op: "Components" } ? Record<string, unknown> :
+    A extends { op: "Set" } ? null :
+    A extends { op: "Watch" } ? Record<string, unknown> :
+    A extends { op: "Unwatch" } ? null :
+    unknown
+  ) :
+  T extends "Command" ? (
+    A extends { command: "Spawn" } ? number :
+    A extends { command: "Insert" } ? null :
+    A extends { command: "Remove" } ? null :
+    A extends { command: "Despawn" } ? null :
+    A extends { command: "SendEvent" } ? null :
+    unknown
+  ) :
+  unknown;
+
+/** The data pushed on each channel, see `Outbox::push`. */
+export interface Channels {
+  inspector: InspectorDiff;
+  sync: SyncPush;
+  log: WebLogRecord;
+}
+
+/** `bevy.inspector`, see `inspector::INSPECTOR_SCRIPT`. */
+export interface Inspector {
+  entities(): Promise<CallResult<"Inspector", { op: "Entities" }>>;
+  components(entity: number): Promise<CallResult<"Inspector", { op: "Components" }>>;
+  set(entity: number, component: string, value: unknown): Promise<CallResult<"Inspector", { op: "Set" }>>;
+  /** The callback is first called with all the components of the entity. Resolves to a function unwatching. */
+  watch(entity: number, callback: (diff: InspectorDiff) => void): Promise<() => void>;
+}
+
+/** `bevy.commands`, see `web_commands::COMMANDS_SCRIPT`. */
+export interface Commands {
+  spawn(components?: Record<string, unknown>): Promise<CallResult<"Command", { command: "Spawn" }>>;
+  insert(entity: number, components: Record<string, unknown>): Promise<CallResult<"Command", { command: "Insert" }>>;
+  remove(entity: number, components: Array<string>): Promise<CallResult<"Command", { command: "Remove" }>>;
+  despawn(entity: number): Promise<CallResult<"Command", { command: "Despawn" }>>;
+  sendEvent(event: string, value: unknown): Promise<CallResult<"Command", { command: "SendEvent" }>>;
+}
+
+/** `bevy.buffers`, see `binary::BUFFERS_SCRIPT`. */
+export interface Buffers {
+  url(id: string): string;
+  fetch(id: string): Promise<ArrayBuffer>;
+  upload(id: string, bytes: BodyInit): Promise<void>;
+  release(id: string): Promise<void>;
+}
+
+/** The `window.bevy` object, see `bridge::BRIDGE_SCRIPT`. */
+export interface Bevy {
+  /** Post a request without answer. */
+  send(request: Exclude<Request, { id: number }>): void;
+  /** Post a request, and get its answer. */
+  call<T extends CallType, A extends CallArgs<T>>(type: T, args: A): Promise<CallResult<T, A>>;
+  /** Listen to the data pushed on `channel`, returns a function removing the listener. */
+  on<C extends keyof Channels>(channel: C, callback: (data: Channels[C]) => void): () => void;
+  /** Get the value of a synced store, and its changes. Returns a function unsubscribing. */
+  subscribe(store: string, callback: (value: unknown) => void): () => void;
+  inspector: Inspector;
+  commands: Commands;
+  buffers: Buffers;
+}
+
+declare global {
+  interface Window {
+    bevy: Bevy;
+  }
+  const bevy: Bevy;
+}